@@ -0,0 +1,169 @@
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use libcxgraph::{language::{compile, free_variables}, renderer::WgpuState, session::{Session, SessionFormat}, timeline::Timeline};
+
+use self::output::Output;
+
+mod output;
+
+const USAGE: &str = "\
+usage: cxgraph-cli <program> <timeline> <output> [options]
+       cxgraph-cli <session> <output> [options]
+
+Render an animation of the program following the keyframes in the timeline,
+a JSON file, or of a session saved as a .json or .toml file with a timeline.
+If output ends in .png or .gif an animated image is written, otherwise it is
+a directory that the frames are written to as numbered PNGs.
+
+options:
+  --size <width>x<height>            size in pixels, default 800x800
+  --bounds <x0>,<y0>,<x1>,<y1>       view when the timeline does not animate it
+  --var <name>=<re>[,<im>]           value of a variable the timeline does not animate";
+
+enum Input {
+	Program { program: PathBuf, timeline: PathBuf },
+	Session(PathBuf),
+}
+
+struct Args {
+	input: Input,
+	output: PathBuf,
+	size: (u32, u32),
+	bounds: Option<[f32; 4]>,
+	vars: Vec<(String, [f32; 2])>,
+}
+
+fn parse_numbers<const N: usize>(s: &str, sep: char) -> Option<[f32; N]> {
+	let numbers: Vec<f32> = s.split(sep).map(|n| n.trim().parse().ok()).collect::<Option<_>>()?;
+	numbers.try_into().ok()
+}
+
+fn parse_args() -> Result<Args, String> {
+	let mut positional = Vec::new();
+	let mut size = (800, 800);
+	let mut bounds = None;
+	let mut vars = Vec::new();
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().ok_or_else(|| format!("missing value for {name}"));
+		match arg.as_str() {
+			"--size" => {
+				let value = value("--size")?;
+				let [w, h] = parse_numbers::<2>(&value, 'x')
+					.filter(|[w, h]| w.fract() == 0.0 && h.fract() == 0.0 && *w >= 1.0 && *h >= 1.0)
+					.ok_or_else(|| format!("invalid size {value}"))?;
+				size = (w as u32, h as u32);
+			}
+			"--bounds" => {
+				let value = value("--bounds")?;
+				bounds = Some(parse_numbers::<4>(&value, ',').ok_or_else(|| format!("invalid bounds {value}"))?);
+			}
+			"--var" => {
+				let value = value("--var")?;
+				let (name, number) = value.split_once('=').ok_or_else(|| format!("invalid variable {value}"))?;
+				let number = parse_numbers::<2>(number, ',')
+					.or_else(|| parse_numbers::<1>(number, ',').map(|[re]| [re, 0.0]))
+					.ok_or_else(|| format!("invalid value for variable {name}"))?;
+				vars.push((name.to_owned(), number));
+			}
+			"--help" | "-h" => return Err(USAGE.to_owned()),
+			_ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+			_ => positional.push(PathBuf::from(arg)),
+		}
+	}
+	let (input, output) = match <[PathBuf; 3]>::try_from(positional) {
+		Ok([program, timeline, output]) => (Input::Program { program, timeline }, output),
+		Err(positional) => match <[PathBuf; 2]>::try_from(positional) {
+			Ok([session, output]) => (Input::Session(session), output),
+			Err(_) => return Err(USAGE.to_owned()),
+		},
+	};
+	Ok(Args { input, output, size, bounds, vars })
+}
+
+// the session to render and its timeline. a program on its own
+// gets the default view and settings, with the view fit to the size
+fn read_input(input: &Input, size: (u32, u32)) -> Result<(Session, Timeline), Box<dyn Error>> {
+	match input {
+		Input::Program { program, timeline } => {
+			let src = std::fs::read_to_string(program)?;
+			let timeline = serde_json::from_str(&std::fs::read_to_string(timeline)?)?;
+			let aspect = size.0 as f64 / size.1 as f64;
+			Ok((Session::new(src, [-3.0 * aspect, -3.0], [3.0 * aspect, 3.0]), timeline))
+		}
+		Input::Session(path) => {
+			let format = path.extension()
+				.and_then(|ext| SessionFormat::from_extension(&ext.to_string_lossy()))
+				.ok_or("session files should end in .json or .toml")?;
+			let mut session = Session::load(&std::fs::read_to_string(path)?, format)?;
+			let timeline = session.timeline.take().ok_or("the session has no timeline")?;
+			Ok((session, timeline))
+		}
+	}
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+	let (session, timeline) = read_input(&args.input, args.size)?;
+	timeline.validate()?;
+	let src = &session.source;
+
+	// every name the program needs is made a variable, defaulting to zero
+	let mut vars: HashMap<String, usize> = free_variables(src, &[])?
+		.into_iter()
+		.enumerate()
+		.map(|(slot, (name, _))| (name.to_owned(), slot))
+		.collect();
+	let program = compile(src, &vars, &[])?;
+	for warning in &program.warnings {
+		eprintln!("warning: {warning}");
+	}
+
+	let mut state = pollster::block_on(WgpuState::new_headless(args.size));
+	session.write_uniforms(&mut state.uniforms);
+	if let Some([x0, y0, x1, y1]) = args.bounds {
+		state.uniforms.bounds_min = (x0, y0);
+		state.uniforms.bounds_max = (x1, y1);
+	}
+	for param in &program.params {
+		state.variables[2*param.slot] = param.default.re as f32;
+		state.variables[2*param.slot + 1] = param.default.im as f32;
+		vars.insert(param.name.clone(), param.slot);
+	}
+	// the session may have variables the program no longer uses
+	for var in session.variables.iter().filter(|var| vars.contains_key(var.name())) {
+		let slot = vars[var.name()];
+		let [re, im] = var.value();
+		state.variables[2*slot] = re as f32;
+		state.variables[2*slot + 1] = im as f32;
+	}
+	for (name, [re, im]) in args.vars {
+		let slot = vars.get(&name).ok_or_else(|| format!("the program does not use a variable {name}"))?;
+		state.variables[2*slot] = re;
+		state.variables[2*slot + 1] = im;
+	}
+	state.load_shaders(&program.wgsl, program.constants);
+
+	let frame_count = timeline.frame_count();
+	let mut output = Output::create(&args.output, args.size, frame_count, timeline.fps)?;
+	for frame in 0..frame_count {
+		timeline.apply(&mut state, &vars, frame);
+		output.add_frame(args.size, timeline.fps, state.render_image())?;
+		eprint!("\rrendered frame {}/{frame_count}", frame + 1);
+	}
+	eprintln!();
+	output.finish()
+}
+
+fn main() {
+	let args = match parse_args() {
+		Ok(args) => args,
+		Err(msg) => {
+			eprintln!("{msg}");
+			std::process::exit(2);
+		}
+	};
+	if let Err(e) = run(args) {
+		eprintln!("error: {e}");
+		std::process::exit(1);
+	}
+}
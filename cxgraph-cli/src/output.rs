@@ -0,0 +1,78 @@
+use std::{error::Error, fs::File, io::BufWriter, path::{Path, PathBuf}};
+
+/// Where rendered frames are written
+pub enum Output {
+	/// Numbered PNG files in a directory
+	Frames { dir: PathBuf, next: u32 },
+	AnimatedPng(png::Writer<BufWriter<File>>),
+	Gif(gif::Encoder<BufWriter<File>>),
+}
+
+impl Output {
+	/// Choose the output from the path: an animated image for a `.gif` or `.png`
+	/// file, otherwise numbered PNG files in the directory at the path
+	pub fn create(path: &Path, size: (u32, u32), frame_count: u32, fps: f64) -> Result<Self, Box<dyn Error>> {
+		let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase);
+		match extension.as_deref() {
+			Some("png") => {
+				let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size.0, size.1);
+				encoder.set_color(png::ColorType::Rgba);
+				encoder.set_depth(png::BitDepth::Eight);
+				encoder.set_animated(frame_count, 0)?;
+				// the delay is a fraction of a second with a 16-bit denominator
+				let den = (fps * 100.0).round().clamp(1.0, u16::MAX as f64) as u16;
+				encoder.set_frame_delay(100, den)?;
+				Ok(Output::AnimatedPng(encoder.write_header()?))
+			}
+			Some("gif") => {
+				let (width, height) = gif_size(size)?;
+				let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), width, height, &[])?;
+				encoder.set_repeat(gif::Repeat::Infinite)?;
+				Ok(Output::Gif(encoder))
+			}
+			_ => {
+				std::fs::create_dir_all(path)?;
+				Ok(Output::Frames { dir: path.to_owned(), next: 0 })
+			}
+		}
+	}
+
+	/// Add a frame of RGBA pixels
+	pub fn add_frame(&mut self, size: (u32, u32), fps: f64, mut pixels: Vec<u8>) -> Result<(), Box<dyn Error>> {
+		match self {
+			Output::Frames { dir, next } => {
+				let path = dir.join(format!("frame_{next:05}.png"));
+				let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size.0, size.1);
+				encoder.set_color(png::ColorType::Rgba);
+				encoder.set_depth(png::BitDepth::Eight);
+				encoder.write_header()?.write_image_data(&pixels)?;
+				*next += 1;
+			}
+			Output::AnimatedPng(writer) => writer.write_image_data(&pixels)?,
+			Output::Gif(encoder) => {
+				let (width, height) = gif_size(size)?;
+				let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+				// gif delays are in hundredths of a second
+				frame.delay = (100.0 / fps).round().clamp(1.0, u16::MAX as f64) as u16;
+				encoder.write_frame(&frame)?;
+			}
+		}
+		Ok(())
+	}
+
+	pub fn finish(self) -> Result<(), Box<dyn Error>> {
+		match self {
+			Output::Frames { .. } => (),
+			Output::AnimatedPng(writer) => writer.finish()?,
+			Output::Gif(encoder) => drop(encoder.into_inner()?),
+		}
+		Ok(())
+	}
+}
+
+fn gif_size(size: (u32, u32)) -> Result<(u16, u16), Box<dyn Error>> {
+	match (u16::try_from(size.0), u16::try_from(size.1)) {
+		(Ok(width), Ok(height)) => Ok((width, height)),
+		_ => Err(format!("{}x{} is too large for a gif", size.0, size.1).into()),
+	}
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
-use libcxgraph::{renderer::WgpuState, language::{compile, show_ast}};
-use log::info;
+use libcxgraph::{renderer::{CustomUniform, CustomValue, WgpuState}, language::{compile, free_variables, show_ast, ParamInfo, ParamKind, MAX_VARIABLES}, link, session::{Session, Variable}};
+use log::{info, warn};
 use winit::{window::WindowBuilder, event_loop::EventLoop, platform::web::WindowBuilderExtWebSys};
 use wasm_bindgen::{prelude::*, JsValue};
 use web_sys::HtmlCanvasElement;
@@ -11,7 +11,7 @@ use web_sys::HtmlCanvasElement;
 static mut WGPU_STATE: Option<WgpuState> = None;
 
 fn with_state<F>(f: F)
-where F: Fn(&mut WgpuState) {
+where F: FnOnce(&mut WgpuState) {
 	let mut state = unsafe { WGPU_STATE.take().unwrap() };
 	f(&mut state);
 	unsafe { WGPU_STATE = Some(state) };
@@ -53,16 +53,69 @@ pub async fn start() {
 	info!("Initialized");
 }
 
+/// A parameter declared in the program
+#[wasm_bindgen(getter_with_clone)]
+pub struct Param {
+	pub name: String,
+	/// Either "real" or "point"
+	pub kind: String,
+	pub slot: usize,
+	pub min: Option<f64>,
+	pub max: Option<f64>,
+	pub step: Option<f64>,
+	pub default_re: f64,
+	pub default_im: f64,
+}
+
+impl From<ParamInfo> for Param {
+	fn from(info: ParamInfo) -> Self {
+		let kind = match info.kind {
+			ParamKind::Real => "real",
+			ParamKind::Point => "point",
+		};
+		Self {
+			name: info.name,
+			kind: kind.to_owned(),
+			slot: info.slot,
+			min: info.range.map(|r| r.0),
+			max: info.range.map(|r| r.1),
+			step: info.step,
+			default_re: info.default.re,
+			default_im: info.default.im,
+		}
+	}
+}
+
+fn custom_uniforms() -> Vec<CustomUniform> {
+	let mut custom = Vec::new();
+	with_state(|state| custom = state.custom_uniforms().to_vec());
+	custom
+}
+
+/// Compile and load a program, returning the parameters it declares. Those
+/// that are not among var_names are given the slots after them, in order
 #[wasm_bindgen]
-pub fn load_shader(src: &str, var_names: Box<[JsValue]>) -> Result<(), JsValue> {
+pub fn load_shader(src: &str, var_names: Box<[JsValue]>) -> Result<Vec<Param>, JsValue> {
 	let names: HashMap<String, usize> = var_names.iter()
 		.enumerate()
 		.map(|(i, e)| (e.as_string().unwrap(), i))
 		.collect();
-	let wgsl = compile(src, &names).map_err(|e| e.to_string())?;
-	info!("Generated WGSL:\n{}", wgsl);
-	with_state(|state| state.load_shaders(&wgsl));
-	Ok(())
+	let program = compile(src, &names, &custom_uniforms()).map_err(|e| e.to_string())?;
+	info!("Generated WGSL:\n{}", program.wgsl);
+	for warning in &program.warnings {
+		warn!("{warning}");
+	}
+	with_state(|state| {
+		state.load_shaders(&program.wgsl, program.constants);
+	});
+	Ok(program.params.into_iter().map(Param::from).collect())
+}
+
+/// Names the program uses that would have to be variables, in order of first use
+#[wasm_bindgen]
+pub fn find_variables(src: &str) -> Result<Box<[JsValue]>, JsValue> {
+	let free = free_variables(src, &custom_uniforms()).map_err(|e| e.to_string())?;
+	Ok(free.into_iter().map(|(name, _)| JsValue::from_str(name)).collect())
 }
 
 #[wasm_bindgen]
@@ -109,6 +162,16 @@ pub fn set_coloring(value: u32) {
 	with_state(|state| state.uniforms.coloring = value);
 }
 
+#[wasm_bindgen]
+pub fn set_modulus_base(value: f32) {
+	with_state(|state| state.uniforms.modulus_base = value);
+}
+
+#[wasm_bindgen]
+pub fn set_phase_sectors(value: u32) {
+	with_state(|state| state.uniforms.phase_sectors = value);
+}
+
 #[wasm_bindgen]
 pub fn set_decorations(value: u32) {
 	with_state(|state| state.uniforms.decorations = value);
@@ -119,10 +182,76 @@ pub fn set_grid_mode(value: u32) {
 	with_state(|state| state.uniforms.grid_mode = value);
 }
 
+#[wasm_bindgen]
+pub fn set_iteration_budget(value: u32) {
+	with_state(|state| state.uniforms.iteration_budget = value);
+}
+
+/// Set the time in seconds and the frame number, read as t and frame
+#[wasm_bindgen]
+pub fn set_time(time: f32, frame: u32) {
+	with_state(|state| state.set_time(time, frame));
+}
+
+#[wasm_bindgen]
+pub fn max_variables() -> usize {
+	MAX_VARIABLES
+}
+
 #[wasm_bindgen]
 pub fn set_variable(idx: usize, re: f32, im: f32) {
 	with_state(|state| {
-		state.uniforms.variables[idx*2 + 0] = re;
-		state.uniforms.variables[idx*2 + 1] = im;
+		if let Some(var) = state.variables.get_mut(idx*2..idx*2 + 2) {
+			var[0] = re;
+			var[1] = im;
+		}
 	});
 }
+
+fn custom_value(re: f32, im: Option<f32>) -> CustomValue {
+	match im {
+		Some(im) => CustomValue::Complex(re, im),
+		None => CustomValue::Real(re),
+	}
+}
+
+/// Add a uniform that programs can read by name, real unless im is given.
+/// Programs compiled afterwards can use it once they are loaded
+#[wasm_bindgen]
+pub fn add_uniform(name: &str, re: f32, im: Option<f32>) {
+	with_state(|state| state.add_custom_uniform(name, custom_value(re, im)));
+}
+
+/// Set the value of a uniform added with add_uniform, returning
+/// false if there is none with the name or its type differs
+#[wasm_bindgen]
+pub fn set_uniform(name: &str, re: f32, im: Option<f32>) -> bool {
+	let mut found = false;
+	with_state(|state| found = state.set_custom_uniform(name, custom_value(re, im)));
+	found
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn set_mobius(a_re: f32, a_im: f32, b_re: f32, b_im: f32, c_re: f32, c_im: f32, d_re: f32, d_im: f32) {
+	with_state(|state| state.uniforms.mobius = [a_re, a_im, b_re, b_im, c_re, c_im, d_re, d_im]);
+}
+
+/// Encode the program, the current view and settings, and the variables as
+/// text for a link. variables is a JSON array of variables as in a session
+#[wasm_bindgen]
+pub fn encode_link(src: &str, variables: &str) -> Result<String, JsValue> {
+	let variables: Vec<Variable> = serde_json::from_str(variables).map_err(|e| e.to_string())?;
+	let mut session = Session::new(src.to_owned(), [0.0, 0.0], [0.0, 0.0]);
+	with_state(|state| session.read_uniforms(&state.uniforms));
+	session.variables = variables;
+	Ok(link::encode(&session))
+}
+
+/// Decode text made by encode_link, returning the session as JSON.
+/// Nothing is changed, so a link that cannot be read can be ignored
+#[wasm_bindgen]
+pub fn decode_link(text: &str) -> Result<String, JsValue> {
+	let session = link::decode(text).map_err(|e| e.to_string())?;
+	serde_json::to_string(&session).map_err(|e| e.to_string().into())
+}
@@ -160,8 +160,9 @@ canvas.addEventListener("keydown", onKeyDown);
 function onGraph() {
 	let src = source_text.value;
 	try {
-		cxgraph.load_shader(src, graphView.varNames);
+		let params = cxgraph.load_shader(src, graphView.varNames);
 		div_error_msg.hidden = true;
+		addParams(params);
 		redraw();
 	} catch(e) {
 		console.log(e);
@@ -173,6 +174,40 @@ function onGraph() {
 button_graph.addEventListener("click", onGraph);
 button_redraw.addEventListener("click", redraw);
 
+//
+// Animation
+//
+
+let animation = {
+	time: 0,
+	frame: 0,
+	lastTimestamp: null,
+	running: false,
+};
+
+function onAnimationFrame(timestamp) {
+	if(!checkbox_animate.checked) {
+		animation.lastTimestamp = null;
+		animation.running = false;
+		return;
+	}
+	if(animation.lastTimestamp !== null) {
+		animation.time += (timestamp - animation.lastTimestamp) / 1000;
+		animation.frame += 1;
+	}
+	animation.lastTimestamp = timestamp;
+	cxgraph.set_time(animation.time, animation.frame);
+	redraw();
+	requestAnimationFrame(onAnimationFrame);
+}
+
+checkbox_animate.addEventListener("change", () => {
+	if(checkbox_animate.checked && !animation.running) {
+		animation.running = true;
+		requestAnimationFrame(onAnimationFrame);
+	}
+});
+
 //
 // Options
 //
@@ -228,6 +263,22 @@ for(let e of nameColorMode) {
 nameColorMode[1].checked = true;
 cxgraph.set_coloring(1);
 
+number_modulus_base.addEventListener("change", () => {
+	let value = parseFloat(number_modulus_base.value);
+	if(value > 1) {
+		cxgraph.set_modulus_base(value);
+		tryRedraw();
+	}
+});
+
+number_phase_sectors.addEventListener("change", () => {
+	let value = parseInt(number_phase_sectors.value);
+	if(value >= 1) {
+		cxgraph.set_phase_sectors(value);
+		tryRedraw();
+	}
+});
+
 let nameGridMode = document.getElementsByName("grid_mode");
 for(let e of nameGridMode) {
 	e.addEventListener("change", () => {
@@ -282,9 +333,9 @@ function genVarNames() {
 	onGraph();
 }
 
-function addSlider() {
-	if(varCount >= 8) {
-		return;
+function addSlider(regen = true) {
+	if(varCount >= cxgraph.max_variables()) {
+		return null;
 	}
 	let newSlider = slider_template.cloneNode(true);
 	let id = nextVarId++;
@@ -321,11 +372,14 @@ function addSlider() {
 		genVarData();
 		tryRedraw();
 	});
-	genVarNames();
+	if(regen) {
+		genVarNames();
+	}
+	return newSlider;
 }
 
 class Point {
-	constructor(id) {
+	constructor(id, regen = true) {
 		this.id = id;
 
 		let menuPoint = point_template.cloneNode(true);
@@ -373,7 +427,9 @@ class Point {
 		svgPoint.addEventListener("pointermove", (e) => this.onPointerMove(e));
 
 		this.onViewChange();
-		genVarNames();
+		if(regen) {
+			genVarNames();
+		}
 	}
 
 	onPointerMove(e) {
@@ -404,23 +460,212 @@ class Point {
 	}
 }
 
-function addPoint() {
-	if(varCount >= 8) {
-		return;
+function addPoint(regen = true) {
+	if(varCount >= cxgraph.max_variables()) {
+		return null;
+	}
+	let point = new Point(nextVarId++, regen);
+	graphPoints.push(point);
+	return point;
+}
+
+// add a slider or point for each parameter declared in
+// the program that is not already a variable
+function addParams(params) {
+	let added = false;
+	for(let param of params) {
+		if(graphView.varNames.includes(param.name)) {
+			continue;
+		}
+		let elem;
+		if(param.kind == "point") {
+			let point = addPoint(false);
+			if(point == null) {
+				break;
+			}
+			elem = point.menuPoint;
+			elem.querySelector(".var-value-re").value = param.default_re;
+			elem.querySelector(".var-value-im").value = param.default_im;
+			point.onViewChange();
+		} else {
+			elem = addSlider(false);
+			if(elem == null) {
+				break;
+			}
+			let slider = elem.querySelector(".var-slider");
+			if(param.min !== undefined) {
+				elem.querySelector(".var-min").value = param.min;
+				elem.querySelector(".var-max").value = param.max;
+				slider.min = param.min;
+				slider.max = param.max;
+			}
+			if(param.step !== undefined) {
+				elem.querySelector(".var-step").value = param.step;
+				slider.step = param.step;
+			}
+			slider.value = param.default_re;
+			elem.querySelector(".var-value").value = param.default_re;
+		}
+		elem.querySelector(".var-name").value = param.name;
+		varCount++;
+		added = true;
+	}
+	if(added) {
+		genVarNames();
 	}
-	graphPoints.push(new Point(nextVarId++));
 }
 
-button_slider_new.addEventListener("click", addSlider);
-button_point_new.addEventListener("click", addPoint);
+button_slider_new.addEventListener("click", () => addSlider());
+button_point_new.addEventListener("click", () => addPoint());
+
+
+//
+// Links
+//
+
+// the variables in the form used by sessions
+function getVariables() {
+	let vars = [];
+	for(let child of div_variables.children) {
+		let name = child.querySelector(".var-name").value || "";
+		if(child.id.startsWith("slider")) {
+			vars.push({
+				kind: "slider",
+				name: name,
+				value: parseFloat(child.querySelector(".var-value").value) || 0,
+				min: parseFloat(child.querySelector(".var-min").value) || 0,
+				max: parseFloat(child.querySelector(".var-max").value) || 0,
+				step: parseFloat(child.querySelector(".var-step").value) || 0,
+			});
+		} else if(child.id.startsWith("point")) {
+			vars.push({
+				kind: "point",
+				name: name,
+				value: [
+					parseFloat(child.querySelector(".var-value-re").value) || 0,
+					parseFloat(child.querySelector(".var-value-im").value) || 0,
+				],
+			});
+		}
+	}
+	return vars;
+}
+
+function setVariables(vars) {
+	for(let point of graphPoints) {
+		point.menuPoint.remove();
+		point.svgPoint.remove();
+	}
+	graphPoints = [];
+	div_variables.replaceChildren();
+	varCount = 0;
+	for(let v of vars) {
+		if(v.kind == "point") {
+			let point = addPoint(false);
+			if(point == null) {
+				break;
+			}
+			point.menuPoint.querySelector(".var-name").value = v.name;
+			point.menuPoint.querySelector(".var-value-re").value = v.value[0];
+			point.menuPoint.querySelector(".var-value-im").value = v.value[1];
+			point.onViewChange();
+		} else {
+			let elem = addSlider(false);
+			if(elem == null) {
+				break;
+			}
+			let slider = elem.querySelector(".var-slider");
+			elem.querySelector(".var-name").value = v.name;
+			elem.querySelector(".var-min").value = v.min;
+			elem.querySelector(".var-max").value = v.max;
+			elem.querySelector(".var-step").value = v.step;
+			elem.querySelector(".var-value").value = v.value;
+			slider.min = v.min;
+			slider.max = v.max;
+			slider.step = v.step;
+			slider.value = v.value;
+		}
+		varCount++;
+	}
+}
+
+function setChecked(elems, value) {
+	for(let e of elems) {
+		e.checked = parseInt(e.getAttribute("data-value")) == value;
+	}
+}
+
+// show the session from a link. the view keeps the window's aspect ratio,
+// showing the same vertical range as when the link was made
+function applySession(session) {
+	source_text.value = session.source;
+
+	let [x0, y0] = session.view.bounds_min;
+	let [x1, y1] = session.view.bounds_max;
+	graphView.xoff = (x0 + x1) / 2;
+	graphView.yoff = (y0 + y1) / 2;
+	graphView.scale = (y1 - y0) / 2;
+	cxgraph.set_mobius(...session.view.mobius);
+
+	let settings = session.settings;
+	range_resolution.value = Math.round(Math.log2(settings.res_scale));
+	graphView.res_mult = Math.pow(2, parseFloat(range_resolution.value));
+	range_shading.value = settings.shading_intensity;
+	cxgraph.set_shading_intensity(settings.shading_intensity);
+	range_contour.value = settings.contour_intensity;
+	cxgraph.set_contour_intensity(settings.contour_intensity);
+	for(let e of classDecor) {
+		e.checked = (settings.decorations & parseInt(e.getAttribute("data-value"))) != 0;
+	}
+	cxgraph.set_decorations(settings.decorations);
+	setChecked(nameColorMode, settings.coloring);
+	cxgraph.set_coloring(settings.coloring);
+	number_modulus_base.value = settings.modulus_base;
+	cxgraph.set_modulus_base(settings.modulus_base);
+	number_phase_sectors.value = settings.phase_sectors;
+	cxgraph.set_phase_sectors(settings.phase_sectors);
+	setChecked(nameGridMode, settings.grid_mode);
+	cxgraph.set_grid_mode(settings.grid_mode);
+	cxgraph.set_iteration_budget(settings.iteration_budget);
+
+	setVariables(session.variables);
+}
+
+// graph the link in the URL fragment, if there is one. a link that
+// cannot be read is reported and otherwise ignored
+function openLink() {
+	let text = window.location.hash.slice(1);
+	let error = null;
+	if(text != "") {
+		try {
+			applySession(JSON.parse(cxgraph.decode_link(text)));
+		} catch(e) {
+			console.log(e);
+			error = e;
+		}
+	}
+	genVarNames();
+	onResize();
+	if(error !== null) {
+		div_error_msg.textContent = "Could not open link: " + error.toString();
+		div_error_msg.hidden = false;
+	}
+}
+
+button_link.addEventListener("click", () => {
+	let text = cxgraph.encode_link(source_text.value, JSON.stringify(getVariables()));
+	history.replaceState(null, "", "#" + text);
+	navigator.clipboard.writeText(window.location.href);
+});
+
+window.addEventListener("hashchange", openLink);
 
 
 //
 // Init
 //
 
-onResize();
-onGraph();
+openLink();
 
 // Debug
 
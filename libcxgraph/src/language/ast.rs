@@ -28,45 +28,58 @@ pub enum ExpressionType<'a> {
 	Iter { itervar: &'a str },
 }
 
+/// Byte offsets of the start and end of some source text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct Expression<'a> {
 	pub ty: ExpressionType<'a>,
 	pub children: Vec<Expression<'a>>,
+	pub span: Span,
 }
 
 impl<'a> Expression<'a> {
+	pub fn with_span(self, start: usize, end: usize) -> Self {
+		Self { span: Span { start, end }, ..self }
+	}
+
 	pub fn new_block(exs: Vec<Expression<'a>>) -> Self {
-		Self { ty: ExpressionType::Block, children: exs }
+		Self { ty: ExpressionType::Block, children: exs, span: Span::default() }
 	}
 
 	pub fn new_number(x: f64) -> Self {
-		Self { ty: ExpressionType::Number(Complex::new(x, 0.0)), children: Vec::with_capacity(0) }
+		Self { ty: ExpressionType::Number(Complex::new(x, 0.0)), children: Vec::with_capacity(0), span: Span::default() }
 	}
 
 	pub fn new_name(n: &'a str) -> Self {
-		Self { ty: ExpressionType::Name(n), children: Vec::with_capacity(0) }
+		Self { ty: ExpressionType::Name(n), children: Vec::with_capacity(0), span: Span::default() }
 	}
 
 	pub fn new_unary(op: UnaryOp, arg: Self) -> Self {
-		Self { ty: ExpressionType::Unary(op), children: vec![arg] }
+		Self { ty: ExpressionType::Unary(op), children: vec![arg], span: Span::default() }
 	}
 
 	pub fn new_binary(op: BinaryOp, arg0: Self, arg1: Self) -> Self {
-		Self { ty: ExpressionType::Binary(op), children: vec![arg0, arg1] }
+		Self { ty: ExpressionType::Binary(op), children: vec![arg0, arg1], span: Span::default() }
 	}
 
 	pub fn new_fncall(name: &'a str, args: Vec<Self>) -> Self {
-		Self { ty: ExpressionType::FnCall(name), children: args }
+		Self { ty: ExpressionType::FnCall(name), children: args, span: Span::default() }
 	}
 
 	pub fn new_store(expr: Self, name: &'a str) -> Self {
-		Self { ty: ExpressionType::Store(name), children: vec![expr] }
+		Self { ty: ExpressionType::Store(name), children: vec![expr], span: Span::default() }
 	}
 
 	pub fn new_if(cond: Self, t: Self, f: Self) -> Self {
 		Self {
 			ty: ExpressionType::If,
 			children: vec![cond, t, f],
+			span: Span::default(),
 		}
 	}
 
@@ -74,6 +87,7 @@ impl<'a> Expression<'a> {
 		Self {
 			ty: ExpressionType::While,
 			children: vec![cond, body],
+			span: Span::default(),
 		}
 	}
 
@@ -81,6 +95,7 @@ impl<'a> Expression<'a> {
 		Self {
 			ty: ExpressionType::Sum { countvar },
 			children: vec![min, max, body],
+			span: Span::default(),
 		}
 	}
 
@@ -88,6 +103,7 @@ impl<'a> Expression<'a> {
 		Self {
 			ty: ExpressionType::Prod { countvar },
 			children: vec![min, max, body],
+			span: Span::default(),
 		}
 	}
 
@@ -95,13 +111,79 @@ impl<'a> Expression<'a> {
 		Self {
 			ty: ExpressionType::Iter { itervar },
 			children: vec![count, init, body],
+			span: Span::default(),
 		}
 	}
 }
 
 pub enum Definition<'a> {
-	Constant { name: &'a str, value: Vec<Expression<'a>> },
-	Function { name: &'a str, args: Vec<&'a str>, value: Vec<Expression<'a>> },
+	Constant { name: &'a str, name_span: Span, value: Vec<Expression<'a>> },
+	Function { name: &'a str, name_span: Span, args: Vec<&'a str>, arg_spans: Vec<Span>, value: Vec<Expression<'a>> },
+}
+
+impl<'a> Definition<'a> {
+	pub fn name(&self) -> &'a str {
+		match self {
+			Definition::Constant { name, .. } => name,
+			Definition::Function { name, .. } => name,
+		}
+	}
+
+	pub fn name_span(&self) -> Span {
+		match self {
+			Definition::Constant { name_span, .. } => *name_span,
+			Definition::Function { name_span, .. } => *name_span,
+		}
+	}
+
+	pub fn value(&self) -> &[Expression<'a>] {
+		match self {
+			Definition::Constant { value, .. } => value,
+			Definition::Function { value, .. } => value,
+		}
+	}
+
+	/// The span of the definition's value
+	pub fn span(&self) -> Span {
+		match self.value() {
+			[first, .., last] => Span { start: first.span.start, end: last.span.end },
+			[expr] => expr.span,
+			[] => Span::default(),
+		}
+	}
+}
+
+/// A declaration of a user variable, `param name: kind in [min, max, step] = default`
+pub struct Param<'a> {
+	pub name: &'a str,
+	pub kind: &'a str,
+	pub range: Option<Vec<Expression<'a>>>,
+	pub default: Option<Expression<'a>>,
+	pub span: Span,
+}
+
+pub enum TopLevel<'a> {
+	Definition(Definition<'a>),
+	Param(Param<'a>),
+}
+
+#[derive(Default)]
+pub struct Program<'a> {
+	pub params: Vec<Param<'a>>,
+	pub defns: Vec<Definition<'a>>,
+}
+
+impl<'a> FromIterator<TopLevel<'a>> for Program<'a> {
+	fn from_iter<T: IntoIterator<Item = TopLevel<'a>>>(iter: T) -> Self {
+		let mut program = Program::default();
+		for item in iter {
+			match item {
+				TopLevel::Definition(defn) => program.defns.push(defn),
+				TopLevel::Param(param) => program.params.push(param),
+			}
+		}
+		program
+	}
 }
 
 fn display_expr(w: &mut impl fmt::Write, expr: &Expression, depth: usize) -> fmt::Result {
@@ -127,15 +209,30 @@ fn display_expr(w: &mut impl fmt::Write, expr: &Expression, depth: usize) -> fmt
 	Ok(())
 }
 
+pub fn display_param(w: &mut impl fmt::Write, param: &Param) -> fmt::Result {
+	writeln!(w, "PARAM {} {}", param.name, param.kind)?;
+	if let Some(range) = &param.range {
+		writeln!(w, "  RANGE")?;
+		for expr in range {
+			display_expr(w, expr, 2)?;
+		}
+	}
+	if let Some(default) = &param.default {
+		writeln!(w, "  DEFAULT")?;
+		display_expr(w, default, 2)?;
+	}
+	Ok(())
+}
+
 pub fn display_def(w: &mut impl fmt::Write, def: &Definition) -> fmt::Result {
 	match def {
-		Definition::Constant { name, value } => {
+		Definition::Constant { name, value, .. } => {
 			writeln!(w, "CONSTANT {name}")?;
 			for expr in value {
 				display_expr(w, expr, 1)?;
 			}
 		},
-		Definition::Function { name, args, value } => {
+		Definition::Function { name, args, value, .. } => {
 			writeln!(w, "FUNCTION {name}")?;
 			for arg in args {
 				writeln!(w, "  ARG {arg}")?;
@@ -2,76 +2,136 @@ use std::collections::HashMap;
 
 use num_complex::Complex64 as Complex;
 
+use super::types::Type;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Arity {
+	Fixed(usize),
+	/// At least this many arguments, folded left through the
+	/// two-argument function
+	Variadic(usize),
+}
+
 thread_local! {
-	pub static BUILTIN_FUNCS: HashMap<&'static str, (&'static str, usize)> = {
+	pub static BUILTIN_FUNCS: HashMap<&'static str, (&'static str, Arity)> = {
 		let mut m = HashMap::new();
-		m.insert("pos",   ("c_pos",   1));
-		m.insert("neg",   ("c_neg",   1));
-		m.insert("recip", ("c_recip", 1));
-		m.insert("conj",  ("c_conj",  1));
-
-		m.insert("re",     ("c_re",     1));
-		m.insert("im",     ("c_im",     1));
-		m.insert("signre", ("c_signre", 1));
-		m.insert("signim", ("c_signim", 1));
-		m.insert("absre",  ("c_absre",  1));
-		m.insert("absim",  ("c_absim",  1));
-		m.insert("isnan",  ("c_isnan",  1));
-		m.insert("abs_sq", ("c_abs_sq", 1));
-		m.insert("abs",    ("c_abs",    1));
-		m.insert("arg",    ("c_arg",    1));
-		m.insert("argbr",  ("c_argbr",  2));
-		m.insert("diveu",  ("c_diveu",  2));
-		m.insert("mod",    ("c_mod",    2));
-		m.insert("floor",  ("c_floor",  1));
-		m.insert("ceil",   ("c_ceil",   1));
-		m.insert("round",  ("c_round",  1));
-
-		m.insert("add",   ("c_add",   2));
-		m.insert("sub",   ("c_sub",   2));
-		m.insert("mul",   ("c_mul",   2));
-		m.insert("div",   ("c_div",   2));
-		m.insert("pow",   ("c_pow",   2));
-		m.insert("powbr", ("c_powbr", 3));
-
-		m.insert("exp",    ("c_exp",    1));
-		m.insert("log",    ("c_log",    1));
-		m.insert("log2",   ("c_log2",   1));
-		m.insert("log10",  ("c_log10",  1));
-		m.insert("logb",   ("c_logb",   2));
-		m.insert("logbr",  ("c_logbr",  2));
-		m.insert("sqrt",   ("c_sqrt",   1));
-		m.insert("sqrtbr", ("c_sqrtbr", 2));
-		m.insert("cbrt",   ("c_cbrt",   1));
-		m.insert("cbrtbr", ("c_cbrtbr", 2));
-
-		m.insert("sin",   ("c_sin",   1));
-		m.insert("cos",   ("c_cos",   1));
-		m.insert("tan",   ("c_tan",   1));
-		m.insert("sinh",  ("c_sinh",  1));
-		m.insert("cosh",  ("c_cosh",  1));
-		m.insert("tanh",  ("c_tanh",  1));
-		m.insert("asin",  ("c_asin",  1));
-		m.insert("acos",  ("c_acos",  1));
-		m.insert("atan",  ("c_atan",  1));
-		m.insert("asinh", ("c_asinh", 1));
-		m.insert("acosh", ("c_acosh", 1));
-		m.insert("atanh", ("c_atanh", 1));
-
-		m.insert("gamma",       ("c_gamma",      1));
-		m.insert("\u{0393}",    ("c_gamma",      1));
-		m.insert("invgamma",    ("c_invgamma",   1));
-		m.insert("inv\u{0393}", ("c_invgamma",   1));
-		m.insert("loggamma",    ("c_loggamma",   1));
-		m.insert("log\u{0393}", ("c_loggamma",   1));
-		m.insert("digamma",     ("c_digamma",    1));
-		m.insert("\u{03C8}",    ("c_digamma",    1));
-		m.insert("lambertw",    ("c_lambertw",   1));
-		m.insert("lambertwbr",  ("c_lambertwbr", 2));
-		m.insert("erf",         ("c_erf",        1));
-
-		m.insert("mix", ("c_mix", 3));
+		m.insert("pos",   ("c_pos",   Arity::Fixed(1)));
+		m.insert("neg",   ("c_neg",   Arity::Fixed(1)));
+		m.insert("recip", ("c_recip", Arity::Fixed(1)));
+		m.insert("conj",  ("c_conj",  Arity::Fixed(1)));
+
+		m.insert("re",     ("c_re",     Arity::Fixed(1)));
+		m.insert("im",     ("c_im",     Arity::Fixed(1)));
+		m.insert("signre", ("c_signre", Arity::Fixed(1)));
+		m.insert("signim", ("c_signim", Arity::Fixed(1)));
+		m.insert("absre",  ("c_absre",  Arity::Fixed(1)));
+		m.insert("absim",  ("c_absim",  Arity::Fixed(1)));
+		m.insert("isnan",  ("c_isnan",  Arity::Fixed(1)));
+		m.insert("abs_sq", ("c_abs_sq", Arity::Fixed(1)));
+		m.insert("abs",    ("c_abs",    Arity::Fixed(1)));
+		m.insert("arg",    ("c_arg",    Arity::Fixed(1)));
+		m.insert("argbr",  ("c_argbr",  Arity::Fixed(2)));
+		m.insert("diveu",  ("c_diveu",  Arity::Fixed(2)));
+		m.insert("mod",    ("c_mod",    Arity::Fixed(2)));
+		m.insert("floor",  ("c_floor",  Arity::Fixed(1)));
+		m.insert("ceil",   ("c_ceil",   Arity::Fixed(1)));
+		m.insert("round",  ("c_round",  Arity::Fixed(1)));
+
+		m.insert("add",   ("c_add",   Arity::Fixed(2)));
+		m.insert("sub",   ("c_sub",   Arity::Fixed(2)));
+		m.insert("mul",   ("c_mul",   Arity::Fixed(2)));
+		m.insert("div",   ("c_div",   Arity::Fixed(2)));
+		m.insert("pow",   ("c_pow",   Arity::Fixed(2)));
+		m.insert("powbr", ("c_powbr", Arity::Fixed(3)));
+
+		m.insert("exp",    ("c_exp",    Arity::Fixed(1)));
+		m.insert("log",    ("c_log",    Arity::Fixed(1)));
+		m.insert("log2",   ("c_log2",   Arity::Fixed(1)));
+		m.insert("log10",  ("c_log10",  Arity::Fixed(1)));
+		m.insert("logb",   ("c_logb",   Arity::Fixed(2)));
+		m.insert("logbr",  ("c_logbr",  Arity::Fixed(2)));
+		m.insert("sqrt",   ("c_sqrt",   Arity::Fixed(1)));
+		m.insert("sqrtbr", ("c_sqrtbr", Arity::Fixed(2)));
+		m.insert("cbrt",   ("c_cbrt",   Arity::Fixed(1)));
+		m.insert("cbrtbr", ("c_cbrtbr", Arity::Fixed(2)));
+
+		m.insert("sin",   ("c_sin",   Arity::Fixed(1)));
+		m.insert("cos",   ("c_cos",   Arity::Fixed(1)));
+		m.insert("tan",   ("c_tan",   Arity::Fixed(1)));
+		m.insert("sinh",  ("c_sinh",  Arity::Fixed(1)));
+		m.insert("cosh",  ("c_cosh",  Arity::Fixed(1)));
+		m.insert("tanh",  ("c_tanh",  Arity::Fixed(1)));
+		m.insert("asin",  ("c_asin",  Arity::Fixed(1)));
+		m.insert("acos",  ("c_acos",  Arity::Fixed(1)));
+		m.insert("atan",  ("c_atan",  Arity::Fixed(1)));
+		m.insert("asinh", ("c_asinh", Arity::Fixed(1)));
+		m.insert("acosh", ("c_acosh", Arity::Fixed(1)));
+		m.insert("atanh", ("c_atanh", Arity::Fixed(1)));
+
+		m.insert("gamma",       ("c_gamma",      Arity::Fixed(1)));
+		m.insert("\u{0393}",    ("c_gamma",      Arity::Fixed(1)));
+		m.insert("invgamma",    ("c_invgamma",   Arity::Fixed(1)));
+		m.insert("inv\u{0393}", ("c_invgamma",   Arity::Fixed(1)));
+		m.insert("loggamma",    ("c_loggamma",   Arity::Fixed(1)));
+		m.insert("log\u{0393}", ("c_loggamma",   Arity::Fixed(1)));
+		m.insert("digamma",     ("c_digamma",    Arity::Fixed(1)));
+		m.insert("\u{03C8}",    ("c_digamma",    Arity::Fixed(1)));
+		m.insert("lambertw",    ("c_lambertw",   Arity::Fixed(1)));
+		m.insert("lambertwbr",  ("c_lambertwbr", Arity::Fixed(2)));
+		m.insert("erf",         ("c_erf",        Arity::Fixed(1)));
+		m.insert("erfc",        ("c_erfc",       Arity::Fixed(1)));
+		m.insert("erfi",        ("c_erfi",       Arity::Fixed(1)));
+		m.insert("faddeeva",    ("c_faddeeva",   Arity::Fixed(1)));
+		m.insert("dawson",      ("c_dawson",     Arity::Fixed(1)));
+		m.insert("fresnels",    ("c_fresnels",   Arity::Fixed(1)));
+		m.insert("fresnelc",    ("c_fresnelc",   Arity::Fixed(1)));
+		m.insert("beta",        ("c_beta",       Arity::Fixed(2)));
+		m.insert("gammainc",    ("c_gammainc",   Arity::Fixed(2)));
+		m.insert("gammaincc",   ("c_gammaincc",  Arity::Fixed(2)));
+		m.insert("ei",          ("c_ei",         Arity::Fixed(1)));
+		m.insert("e1",          ("c_e1",         Arity::Fixed(1)));
+		m.insert("li",          ("c_li",         Arity::Fixed(1)));
+		m.insert("polylog",     ("c_polylog",    Arity::Fixed(2)));
+
+		m.insert("mobius",    ("c_mobius",    Arity::Fixed(5)));
+		m.insert("cayley",    ("c_cayley",    Arity::Fixed(1)));
+		m.insert("invcayley", ("c_invcayley", Arity::Fixed(1)));
+		m.insert("blaschke",  ("c_blaschke",  Arity::Fixed(2)));
+		m.insert("hdist",     ("c_hdist",     Arity::Fixed(2)));
 
+		m.insert("rand",      ("c_rand",      Arity::Fixed(2)));
+		m.insert("gaussrand", ("c_gaussrand", Arity::Fixed(2)));
+		m.insert("noise",     ("c_noise",     Arity::Fixed(1)));
+
+		m.insert("min",        ("c_min",        Arity::Variadic(1)));
+		m.insert("max",        ("c_max",        Arity::Variadic(1)));
+		m.insert("clamp",      ("c_clamp",      Arity::Fixed(3)));
+		m.insert("atan2",      ("c_atan2",      Arity::Fixed(2)));
+		m.insert("hypot",      ("c_hypot",      Arity::Fixed(2)));
+		m.insert("sign",       ("c_sign",       Arity::Fixed(1)));
+		m.insert("step",       ("c_step",       Arity::Fixed(2)));
+		m.insert("smoothstep", ("c_smoothstep", Arity::Fixed(3)));
+		m.insert("frac",       ("c_frac",       Arity::Fixed(1)));
+
+		m.insert("mix",  ("c_mix", Arity::Fixed(3)));
+		m.insert("lerp", ("c_mix", Arity::Fixed(3)));
+
+		m.insert("rgb",   ("c_rgb",   Arity::Fixed(3)));
+		m.insert("hsv",   ("c_hsv",   Arity::Fixed(3)));
+		m.insert("oklch", ("c_oklch", Arity::Fixed(3)));
+
+		m.insert("contour", ("c_contour", Arity::Fixed(2)));
+		m.insert("isoline", ("c_isoline", Arity::Fixed(2)));
+
+		m
+	};
+
+	/// Names that read a built in uniform, which change between frames
+	/// rather than being constant, with the code and type of each
+	pub static BUILTIN_UNIFORMS: HashMap<&'static str, (&'static str, Type)> = {
+		let mut m = HashMap::new();
+		m.insert("t",     ("uniforms.time",       Type::Real));
+		m.insert("frame", ("f32(uniforms.frame)", Type::NonNeg));
 		m
 	};
 
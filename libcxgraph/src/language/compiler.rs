@@ -1,13 +1,41 @@
-use std::{collections::{HashSet, HashMap}, fmt};
+use std::{collections::{HashSet, HashMap}, fmt::{self, Write}, hash::{DefaultHasher, Hash, Hasher}};
 
-use super::{ast::{Definition, Expression, ExpressionType, BinaryOp, UnaryOp}, builtins::{BUILTIN_CONSTS, BUILTIN_FUNCS}};
+use crate::renderer::{CustomUniform, CustomValue};
+
+use super::{MAX_VARIABLES, ast::{Definition, Expression, ExpressionType, BinaryOp, Span}, builtins::{Arity, BUILTIN_CONSTS, BUILTIN_FUNCS, BUILTIN_UNIFORMS}, suggest::{builtin_signature, did_you_mean}, types::{self, Type, Value}};
 
 #[derive(Clone, Debug)]
-pub struct CompileError(String);
+pub struct CompileError {
+	msg: String,
+	span: Option<Span>,
+}
+
+impl CompileError {
+	pub fn new(msg: String, span: Option<Span>) -> Self {
+		Self { msg, span }
+	}
+
+	pub fn message(&self) -> &str {
+		&self.msg
+	}
+
+	/// The source text the error was found in, if known
+	pub fn span(&self) -> Option<Span> {
+		self.span
+	}
+
+	fn or_span(self, span: Span) -> Self {
+		Self { span: self.span.or(Some(span)), ..self }
+	}
+}
 
 impl fmt::Display for CompileError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		f.write_str(&self.0)
+		f.write_str(&self.msg)?;
+		if let Some(span) = self.span {
+			write!(f, " at {}:{}", span.start, span.end)?;
+		}
+		Ok(())
 	}
 }
 
@@ -15,13 +43,40 @@ impl std::error::Error for CompileError {}
 
 impl From<String> for CompileError {
 	fn from(value: String) -> Self {
-		Self(value)
+		Self::new(value, None)
 	}
 }
 
 impl From<fmt::Error> for CompileError {
 	fn from(value: fmt::Error) -> Self {
-		Self(value.to_string())
+		Self::new(value.to_string(), None)
+	}
+}
+
+/// A likely mistake in a program that does not stop it compiling
+#[derive(Clone, Debug)]
+pub struct CompileWarning {
+	msg: String,
+	span: Span,
+}
+
+impl CompileWarning {
+	pub(super) fn new(msg: impl Into<String>, span: Span) -> Self {
+		Self { msg: msg.into(), span }
+	}
+
+	pub fn message(&self) -> &str {
+		&self.msg
+	}
+
+	pub fn span(&self) -> Span {
+		self.span
+	}
+}
+
+impl fmt::Display for CompileWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} at {}:{}", self.msg, self.span.start, self.span.end)
 	}
 }
 
@@ -47,100 +102,376 @@ fn format_name(prefix: &str, name: &str) -> String {
 	result
 }
 
+/// The source name of a name made by `format_name`, for reporting errors in generated code
+pub fn source_name(ident: &str) -> Option<String> {
+	let rest = ["func_", "const_", "arg_", "local_", "custom_"].iter()
+		.find_map(|prefix| ident.strip_prefix(prefix))?;
+	let chars: Vec<char> = rest.chars().collect();
+	let mut result = String::with_capacity(rest.len());
+	let mut i = 0;
+	while i < chars.len() {
+		match chars[i..] {
+			['u', '_', ..] => { result.push('_'); i += 2 }
+			['p', '_', ..] => { result.push('\''); i += 2 }
+			['s', d @ '0'..='9', '_', ..] => {
+				result.push(char::from_u32(d as u32 - '0' as u32 + 0x2080).expect("invalid codepoint"));
+				i += 3
+			}
+			[c, ..] => { result.push(c); i += 1 }
+			[] => unreachable!(),
+		}
+	}
+	Some(result)
+}
+
 fn format_func(name: &str) -> String { format_name("func_", name) }
 fn format_const(name: &str) -> String { format_name("const_", name) }
 fn format_arg(name: &str) -> String { format_name("arg_", name) }
 fn format_local(name: &str) -> String { format_name("local_", name) }
+pub fn format_custom_uniform(name: &str) -> String { format_name("custom_", name) }
 fn format_tmp(idx: usize) -> String { format!("tmp_{}", idx) }
 
+// complex values are packed two to a vec4f in the uniform arrays
+fn format_packed(array: &str, idx: usize) -> String {
+	let swizzle = match idx % 2 {
+		0 => "xy",
+		_ => "zw",
+	};
+	format!("{array}[{}].{swizzle}", idx/2)
+}
+
+// generated code for a definition, kept as lines so that each
+// can be tagged with the expression it was generated for
+struct CodeBuf {
+	lines: Vec<(String, Span)>,
+	partial: String,
+	// span of the expression being compiled
+	span: Span,
+}
+
+impl CodeBuf {
+	fn new() -> Self {
+		Self { lines: Vec::new(), partial: String::new(), span: Span::default() }
+	}
+
+	fn len(&self) -> usize {
+		self.lines.len()
+	}
+
+	fn clear(&mut self) {
+		self.lines.clear();
+		self.partial.clear();
+	}
+
+	fn truncate(&mut self, mark: usize) {
+		self.lines.truncate(mark);
+	}
+
+	// insert whole lines before line `mark`
+	fn insert_str(&mut self, mark: usize, code: &str) {
+		let lines = code.lines().map(|line| (line.to_owned(), self.span));
+		self.lines.splice(mark..mark, lines);
+	}
+}
+
+impl fmt::Write for CodeBuf {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let mut parts = s.split('\n');
+		self.partial.push_str(parts.next().unwrap_or_default());
+		for part in parts {
+			let line = std::mem::replace(&mut self.partial, part.to_owned());
+			self.lines.push((line, self.span));
+		}
+		Ok(())
+	}
+}
+
 pub struct Compiler<'w, 'i, W: fmt::Write> {
-	buf: &'w mut W,
+	out: &'w mut W,
+	buf: CodeBuf,
+	// the span each line written to out was generated from
+	line_spans: Vec<Span>,
 	vars: &'w HashMap<String, usize>,
-	global_funcs: HashMap<&'i str, usize>,
-	global_consts: HashSet<&'i str>,
+	// variables declared as parameters with a range
+	bounded_vars: &'w HashSet<&'i str>,
+	host_consts: &'w HashMap<&'i str, usize>,
+	custom_uniforms: &'w [CustomUniform],
+	// argument names and result type
+	global_funcs: HashMap<&'i str, (Vec<&'i str>, Type)>,
+	global_consts: HashMap<&'i str, Type>,
+	// types of the locals of the current definition, see infer_locals
+	local_types: HashMap<&'i str, Type>,
+	// a user variable that each global or local depends on, if any
+	global_deps: HashMap<&'i str, String>,
+	// globals that use isoline, directly or through another global
+	derivative_globals: HashSet<&'i str>,
+	local_deps: HashMap<&'i str, String>,
+	warnings: Vec<CompileWarning>,
+	// hashes of the pure subexpressions of the current definition and
+	// the number of times each occurs, see hash_subexprs and count_subexprs
+	subexpr_hashes: HashMap<*const Expression<'i>, u64>,
+	subexpr_counts: HashMap<u64, usize>,
+	next_tmp: usize,
+	// incremented whenever a local is assigned, so that
+	// subexpressions computed before then can be invalidated
+	stores: usize,
 }
 
 #[derive(Clone)]
 struct LocalState<'i> {
 	local_vars: HashSet<&'i str>,
-	next_tmp: usize,
+	// temporaries holding shared subexpressions: generated
+	// code -> (temporary, value of `stores` when it was computed)
+	shared: HashMap<String, (Value, usize)>,
 }
 
 impl<'i> LocalState<'i> {
 	pub fn new() -> Self {
 		Self {
 			local_vars: HashSet::new(),
-			next_tmp: 0,
+			shared: HashMap::new(),
 		}
 	}
 
-	pub fn next_tmp(&mut self) -> String {
-		let n = self.next_tmp;
-		self.next_tmp += 1;
-		format_tmp(n)
+	// state for the body of a loop, which may run after any
+	// of the locals it reads have been reassigned
+	pub fn loop_body(&self) -> Self {
+		Self {
+			local_vars: self.local_vars.clone(),
+			shared: HashMap::new(),
+		}
+	}
+}
+
+// structural hashes of the compound subexpressions of expr that have no
+// side effects, keyed by address. returns None if expr has side effects
+fn hash_subexprs<'i>(expr: &Expression<'i>, hashes: &mut HashMap<*const Expression<'i>, u64>) -> Option<u64> {
+	let mut pure = matches!(expr.ty, ExpressionType::Number(_) | ExpressionType::Name(_)
+		| ExpressionType::Binary(_) | ExpressionType::Unary(_) | ExpressionType::FnCall(_));
+	let mut hasher = DefaultHasher::new();
+	format!("{:?}", expr.ty).hash(&mut hasher);
+	for child in &expr.children {
+		match hash_subexprs(child, hashes) {
+			Some(h) => h.hash(&mut hasher),
+			None => pure = false,
+		}
+	}
+	if !pure {
+		return None
+	}
+	let hash = hasher.finish();
+	if !expr.children.is_empty() {
+		hashes.insert(expr, hash);
+	}
+	Some(hash)
+}
+
+// count the occurrences of each hashed subexpression. once an expression has
+// been seen its children are not counted again, since they will not be recompiled
+fn count_subexprs(expr: &Expression, hashes: &HashMap<*const Expression, u64>, counts: &mut HashMap<u64, usize>) {
+	if let Some(hash) = hashes.get(&(expr as *const _)) {
+		let count = counts.entry(*hash).or_insert(0);
+		*count += 1;
+		if *count > 1 {
+			return
+		}
+	}
+	for child in &expr.children {
+		count_subexprs(child, hashes, counts);
+	}
+}
+
+// colors can be returned, stored and chosen between by an if,
+// but are not numbers and cannot be used in anything else
+fn expect_number(value: &Value) -> Result<(), CompileError> {
+	if value.ty == Type::Color {
+		return Err("expected a number, found a color".to_owned().into())
+	}
+	Ok(())
+}
+
+// names, uniforms and literals are cheap enough to repeat
+fn is_simple(code: &str) -> bool {
+	match code.strip_prefix("vec2f(") {
+		Some(rest) => !rest.contains('('),
+		None => !code.contains('('),
 	}
 }
 
 impl<'w, 'i, W: fmt::Write> Compiler<'w, 'i, W> {
-	pub fn new(buf: &'w mut W, vars: &'w HashMap<String, usize>) -> Self {
+	pub fn new(out: &'w mut W, vars: &'w HashMap<String, usize>, bounded_vars: &'w HashSet<&'i str>,
+	host_consts: &'w HashMap<&'i str, usize>, custom_uniforms: &'w [CustomUniform]) -> Self {
 		Self {
-			buf,
+			out,
+			buf: CodeBuf::new(),
+			line_spans: Vec::new(),
 			vars,
-			global_consts: HashSet::new(),
+			bounded_vars,
+			host_consts,
+			custom_uniforms,
+			global_consts: HashMap::new(),
 			global_funcs: HashMap::new(),
+			local_types: HashMap::new(),
+			global_deps: HashMap::new(),
+			derivative_globals: HashSet::new(),
+			local_deps: HashMap::new(),
+			warnings: Vec::new(),
+			subexpr_hashes: HashMap::new(),
+			subexpr_counts: HashMap::new(),
+			next_tmp: 0,
+			stores: 0,
+		}
+	}
+
+	/// Declare the uniform array holding the user variables,
+	/// sized for the variables given to the compiler
+	pub fn declare_variables(&mut self) -> Result<(), CompileError> {
+		let count = self.vars.values().map(|var| var + 1).max().unwrap_or(0);
+		if count > MAX_VARIABLES {
+			return Err(format!("program uses {count} variables, but at most {MAX_VARIABLES} are supported").into())
 		}
+		writeln!(self.out, "@group(0) @binding(2) var<uniform> variables: array<vec4f, {}>;", count.div_ceil(2).max(1))?;
+		self.line_spans.push(Span::default());
+		Ok(())
 	}
 
 	pub fn compile_defn(&mut self, defn: &Definition<'i>) -> Result<(), CompileError> {
+		self.buf.clear();
+		self.compile_defn_inner(defn)?;
+		for (line, span) in &self.buf.lines {
+			writeln!(self.out, "{line}")?;
+			self.line_spans.push(*span);
+		}
+		Ok(())
+	}
+
+	/// The source span each line of the generated code came from
+	pub fn line_spans(&self) -> &[Span] {
+		&self.line_spans
+	}
+
+	pub fn take_warnings(&mut self) -> Vec<CompileWarning> {
+		std::mem::take(&mut self.warnings)
+	}
+
+	/// Check a definition and declare its name without emitting it,
+	/// for definitions that `plot` never uses
+	pub fn check_defn(&mut self, defn: &Definition<'i>) -> Result<(), CompileError> {
+		self.buf.clear();
+		self.compile_defn_inner(defn)
+	}
+
+	fn compile_defn_inner(&mut self, defn: &Definition<'i>) -> Result<(), CompileError> {
+		self.next_tmp = 0;
+		self.stores = 0;
+		self.buf.span = defn.span();
+		self.subexpr_hashes.clear();
+		self.subexpr_counts.clear();
+		for expr in defn.value() {
+			hash_subexprs(expr, &mut self.subexpr_hashes);
+		}
+		for expr in defn.value() {
+			count_subexprs(expr, &self.subexpr_hashes, &mut self.subexpr_counts);
+		}
+
 		match defn {
-			Definition::Function { name, args, value } => {
-				if self.global_consts.contains(name) || self.global_funcs.contains_key(name) {
-					return Err(format!("name {name} is already declared in global scope").into())
+			Definition::Function { name, args, arg_spans, value, .. } => {
+				if self.global_consts.contains_key(name) || self.global_funcs.contains_key(name) {
+					let msg = format!("name {name} is already declared in global scope");
+					return Err(CompileError::new(msg, Some(defn.name_span())))
 				}
-				write!(self.buf, "fn {}(", format_func(name))?;
-				for arg in args {
-					write!(self.buf, "{}: vec2f, ", format_arg(arg))?;
+				if let Some((i, arg)) = args.iter().enumerate().find(|(i, arg)| args[..*i].contains(arg)) {
+					let msg = format!("argument {arg} of {name} is declared more than once");
+					return Err(CompileError::new(msg, Some(arg_spans[i])))
 				}
-				writeln!(self.buf, ") -> vec2f {{")?;
+				self.infer_locals(args, value);
+				self.check_derivatives(name, args, value)?;
 
+				let mark = self.buf.len();
 				let mut local = LocalState::new();
 				for arg in args {
 					writeln!(self.buf, "var {} = {};", format_local(arg), format_arg(arg))?;
 					local.local_vars.insert(arg);
 				}
 
-				let mut last = String::with_capacity(0);
+				let mut last = Value::new(String::new(), Type::Complex);
 				for expr in value {
 					last = self.compile_expr(&mut local, expr)?;
 				}
-				writeln!(self.buf, "return {last};\n}}")?;
+				// the fragment shader expects plot to return a vec2f, color a vec3f and decor a number
+				let ty = match *name {
+					"plot" if last.ty == Type::Color => {
+						let msg = "plot must return a number, not a color".to_owned();
+						return Err(CompileError::new(msg, value.last().map(|e| e.span)))
+					}
+					"plot" => Type::Complex,
+					"color" if last.ty != Type::Color => {
+						let msg = "color must return a color, made with rgb, hsv or oklch".to_owned();
+						return Err(CompileError::new(msg, value.last().map(|e| e.span)))
+					}
+					"decor" if last.ty == Type::Color => {
+						let msg = "decor must return a number, not a color".to_owned();
+						return Err(CompileError::new(msg, value.last().map(|e| e.span)))
+					}
+					_ => last.ty,
+				};
+				self.return_value(value, &last.to(ty))?;
+
+				let mut sig = format!("fn {}(", format_func(name));
+				for arg in args {
+					write!(sig, "{}: vec2f, ", format_arg(arg))?;
+				}
+				writeln!(sig, ") -> {} {{", ty.wgsl())?;
+				self.buf.insert_str(mark, &sig);
 
-				self.global_funcs.insert(name, args.len());
+				self.global_funcs.insert(name, (args.clone(), ty));
+				self.record_dependency(name, value);
 				Ok(())
 			}
-			Definition::Constant { name, value } => {
-				if self.global_consts.contains(name) || self.global_funcs.contains_key(name) {
-					return Err(format!("name {name} is already declared in global scope").into())
+			Definition::Constant { name, value, .. } => {
+				if self.global_consts.contains_key(name) || self.global_funcs.contains_key(name) {
+					let msg = format!("name {name} is already declared in global scope");
+					return Err(CompileError::new(msg, Some(defn.name_span())))
 				}
+				self.infer_locals(&[], value);
+				self.check_derivatives(name, &[], value)?;
 
-				writeln!(self.buf, "fn {}() -> vec2f {{", format_const(name))?;
+				let mark = self.buf.len();
 				let mut local = LocalState::new();
 
-				let mut last = String::with_capacity(0);
+				let mut last = Value::new(String::new(), Type::Complex);
 				for expr in value {
 					last = self.compile_expr(&mut local, expr)?;
 				}
-				writeln!(self.buf, "return {last};\n}}")?;
+				self.return_value(value, &last.code)?;
+				self.buf.insert_str(mark, &format!("fn {}() -> {} {{\n", format_const(name), last.ty.wgsl()));
 
-				self.global_consts.insert(name);
+				self.global_consts.insert(name, last.ty);
+				self.record_dependency(name, value);
 				Ok(())
 			}
 		}
 	}
 
+	// write a line attributed to expr rather than to the expression being compiled
+	fn with_span(&mut self, expr: Option<&Expression>, f: impl FnOnce(&mut CodeBuf) -> fmt::Result) -> fmt::Result {
+		let span = self.buf.span;
+		if let Some(expr) = expr {
+			self.buf.span = expr.span;
+		}
+		let result = f(&mut self.buf);
+		self.buf.span = span;
+		result
+	}
+
+	fn return_value(&mut self, value: &[Expression], code: &str) -> fmt::Result {
+		self.with_span(value.last(), |buf| writeln!(buf, "return {code};"))?;
+		writeln!(self.buf, "}}")
+	}
+
 	pub fn ensure_plot_defined(&self) -> Result<(), CompileError> {
-		if let Some(n) = self.global_funcs.get("plot") {
-			if *n == 1 {
+		if let Some((args, _)) = self.global_funcs.get("plot") {
+			if args.len() == 1 {
 				Ok(())
 			} else {
 				Err("Plot function has wrong number of arguments".to_owned().into())
@@ -150,196 +481,607 @@ impl<'w, 'i, W: fmt::Write> Compiler<'w, 'i, W> {
 		}
 	}
 
-	fn compile_expr(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
+	/// Define the function the fragment shader colors the plot with, which is
+	/// the program's color function if it has one, or else the built in colorings
+	pub fn define_plot_color(&mut self) -> Result<(), CompileError> {
+		let color = match self.global_funcs.get("color") {
+			Some((args, _)) if args.len() != 1 => return Err("Color function has wrong number of arguments".to_owned().into()),
+			Some(_) => format!("{}(w)", format_func("color")),
+			None => "color_result(w)".to_owned(),
+		};
+		writeln!(self.out, "fn plot_color(w: vec2f) -> vec3f {{\n\treturn {color};\n}}")?;
+		self.line_spans.extend([Span::default(); 3]);
+		Ok(())
+	}
+
+	/// Define the function the fragment shader shades the plot with after the built in
+	/// decorations, which is the real part of the program's decor function or zero
+	pub fn define_plot_decor(&mut self) -> Result<(), CompileError> {
+		let decor = match self.global_funcs.get("decor") {
+			Some((args, _)) if args.len() != 1 => return Err("Decor function has wrong number of arguments".to_owned().into()),
+			Some((_, ty)) => Value::new(format!("{}(w)", format_func("decor")), *ty).real_part(),
+			None => "0.0".to_owned(),
+		};
+		writeln!(self.out, "fn plot_decor(w: vec2f) -> f32 {{\n\treturn {decor};\n}}")?;
+		self.line_spans.extend([Span::default(); 3]);
+		Ok(())
+	}
+
+	// find the type of every local in a definition. a local is real if every
+	// value stored to it is, which may depend on the types of other locals,
+	// so start by assuming they are all real and widen until nothing changes
+	fn infer_locals(&mut self, args: &[&'i str], value: &[Expression<'i>]) {
+		self.local_types.clear();
+		self.local_deps.clear();
+		for arg in args {
+			self.local_types.insert(arg, Type::Complex);
+		}
+		loop {
+			let before = (self.local_types.clone(), self.local_deps.len());
+			let mut scope: HashSet<&str> = args.iter().copied().collect();
+			for expr in value {
+				self.infer_expr(&mut scope, expr);
+			}
+			if (&self.local_types, self.local_deps.len()) == (&before.0, before.1) {
+				break
+			}
+		}
+	}
+
+	fn depend(&mut self, local: &'i str, scope: &HashSet<&str>, expr: &Expression) {
+		if !self.local_deps.contains_key(local) {
+			if let Some(var) = self.var_dependency(scope, expr) {
+				self.local_deps.insert(local, var);
+			}
+		}
+	}
+
+	// a user variable that the value of expr depends on, if any. this
+	// only follows data, not which branch of an if or loop is taken
+	fn var_dependency(&self, scope: &HashSet<&str>, expr: &Expression) -> Option<String> {
+		let dep = match expr.ty {
+			ExpressionType::Name(n) if scope.contains(n) => self.local_deps.get(n).cloned(),
+			ExpressionType::Name(n) if self.global_consts.contains_key(n) => self.global_deps.get(n).cloned(),
+			ExpressionType::Name(n) if self.vars.contains_key(n) => (!self.bounded_vars.contains(n)).then(|| n.to_owned()),
+			ExpressionType::FnCall(f) => self.global_deps.get(f).cloned(),
+			_ => None,
+		};
+		dep.or_else(|| expr.children.iter().find_map(|child| self.var_dependency(scope, child)))
+	}
+
+	fn record_dependency(&mut self, name: &'i str, value: &[Expression]) {
+		let scope = self.local_types.keys().copied().collect();
+		if let Some(var) = value.iter().find_map(|expr| self.var_dependency(&scope, expr)) {
+			self.global_deps.insert(name, var);
+		}
+	}
+
+	// isoline takes the derivative of its argument, which WGSL only allows in
+	// uniform control flow. every loop may be left early when the iteration
+	// budget runs out, so as well as inside an if or a loop, a derivative
+	// cannot be taken anywhere after a loop in the same definition
+	fn check_derivatives(&mut self, name: &'i str, args: &[&'i str], value: &[Expression<'i>]) -> Result<(), CompileError> {
+		let mut scope = args.iter().copied().collect();
+		let mut uniform = true;
+		let mut uses = false;
+		for expr in value {
+			uses |= self.derivative_use(&mut scope, &mut uniform, expr)?;
+		}
+		if uses {
+			self.derivative_globals.insert(name);
+		}
+		Ok(())
+	}
+
+	// whether expr takes a derivative, failing if it does so where
+	// control flow may not be uniform
+	fn derivative_use(&self, scope: &mut HashSet<&'i str>, uniform: &mut bool, expr: &Expression<'i>) -> Result<bool, CompileError> {
+		let mut uses = false;
+		match expr.ty {
+			ExpressionType::Store(var) => {
+				uses = self.derivative_use(scope, uniform, &expr.children[0])?;
+				scope.insert(var);
+			}
+			ExpressionType::Block => {
+				let mut block_scope = scope.clone();
+				for child in &expr.children {
+					uses |= self.derivative_use(&mut block_scope, uniform, child)?;
+				}
+			}
+			ExpressionType::If => {
+				uses = self.derivative_use(scope, uniform, &expr.children[0])?;
+				for branch in &expr.children[1..] {
+					uses |= self.derivative_use(&mut scope.clone(), &mut false, branch)?;
+				}
+			}
+			ExpressionType::While => {
+				let mut loop_scope = scope.clone();
+				for child in &expr.children {
+					uses |= self.derivative_use(&mut loop_scope, &mut false, child)?;
+				}
+				*uniform = false;
+			}
+			ExpressionType::Sum { countvar: var }
+			| ExpressionType::Prod { countvar: var }
+			| ExpressionType::Iter { itervar: var } => {
+				uses = self.derivative_use(scope, uniform, &expr.children[0])?;
+				uses |= self.derivative_use(scope, uniform, &expr.children[1])?;
+				let mut loop_scope = scope.clone();
+				loop_scope.insert(var);
+				uses |= self.derivative_use(&mut loop_scope, &mut false, &expr.children[2])?;
+				*uniform = false;
+			}
+			_ => {
+				for child in &expr.children {
+					uses |= self.derivative_use(scope, uniform, child)?;
+				}
+			}
+		}
+
+		let (used, msg) = match expr.ty {
+			ExpressionType::FnCall(f) if self.global_funcs.contains_key(f) => (
+				self.derivative_globals.contains(f),
+				format!("{f} uses isoline, so it cannot be called"),
+			),
+			ExpressionType::FnCall(f) => (
+				BUILTIN_FUNCS.with(|c| c.get(f).is_some_and(|(func, _)| *func == "c_isoline")),
+				"isoline cannot be used".to_owned(),
+			),
+			ExpressionType::Name(n) if !scope.contains(n) => (
+				self.derivative_globals.contains(n),
+				format!("{n} uses isoline, so it cannot be used"),
+			),
+			_ => (false, String::new()),
+		};
+		if used && !*uniform {
+			let msg = format!("{msg} inside an if or a loop, or after a loop in the same definition; \
+				a loop can be moved into a function of its own");
+			return Err(CompileError::new(msg, Some(expr.span)))
+		}
+		Ok(uses || used)
+	}
+
+	fn check_loop_bound(&mut self, local: &LocalState, bound: &Expression) {
+		if let Some(var) = self.var_dependency(&local.local_vars, bound) {
+			self.warnings.push(CompileWarning::new(
+				format!("number of loop iterations depends on variable {var}, which has no known range"),
+				bound.span,
+			));
+		}
+	}
+
+	fn widen_local(&mut self, name: &'i str, ty: Type) -> Type {
+		let local_ty = self.local_types.entry(name).or_insert(Type::NonNeg);
+		*local_ty = local_ty.join(ty);
+		*local_ty
+	}
+
+	// the type of the code compile_expr would generate for expr, given the
+	// current guess at the types of locals. this follows the scoping in
+	// compile_expr, and anything that fails to compile is left as complex
+	fn infer_expr(&mut self, scope: &mut HashSet<&'i str>, expr: &Expression<'i>) -> Value {
+		let untyped = |ty| Value::new(String::new(), ty);
+		match expr.ty {
+			ExpressionType::Number(n) => Value::number(n),
+			ExpressionType::Name(v) => untyped(self.resolve_var(scope, v).map_or(Type::Complex, |v| v.ty)),
+			ExpressionType::Store(var) => {
+				let a = self.infer_expr(scope, &expr.children[0]);
+				self.depend(var, scope, &expr.children[0]);
+				scope.insert(var);
+				untyped(self.widen_local(var, a.ty))
+			}
+			ExpressionType::Block => {
+				let mut block_scope = scope.clone();
+				let mut last = untyped(Type::Complex);
+				for child in &expr.children {
+					last = self.infer_expr(&mut block_scope, child);
+				}
+				untyped(last.ty)
+			}
+			ExpressionType::Binary(op) => {
+				let a = self.infer_expr(scope, &expr.children[0]);
+				let b = self.infer_expr(scope, &expr.children[1]);
+				types::binary(op, &a, &b)
+			}
+			ExpressionType::Unary(op) => {
+				let a = self.infer_expr(scope, &expr.children[0]);
+				types::unary(op, &a)
+			}
+			ExpressionType::FnCall(f) => {
+				let args: Vec<Value> = expr.children.iter()
+					.map(|child| self.infer_expr(scope, child))
+					.collect();
+				self.call(f, args).unwrap_or_else(|_| untyped(Type::Complex))
+			}
+			ExpressionType::If => {
+				self.infer_expr(scope, &expr.children[0]);
+				let t = self.infer_expr(&mut scope.clone(), &expr.children[1]);
+				let f = self.infer_expr(&mut scope.clone(), &expr.children[2]);
+				untyped(t.ty.join(f.ty))
+			}
+			ExpressionType::While => {
+				let mut loop_scope = scope.clone();
+				self.infer_expr(&mut loop_scope, &expr.children[0]);
+				let body = self.infer_expr(&mut loop_scope, &expr.children[1]);
+				untyped(body.ty)
+			}
+			ExpressionType::Sum { countvar }
+			| ExpressionType::Prod { countvar } => {
+				self.infer_expr(scope, &expr.children[0]);
+				self.infer_expr(scope, &expr.children[1]);
+				self.widen_local(countvar, Type::Real);
+				let mut loop_scope = scope.clone();
+				loop_scope.insert(countvar);
+				let body = self.infer_expr(&mut loop_scope, &expr.children[2]);
+				untyped(body.ty)
+			}
+			ExpressionType::Iter { itervar } => {
+				self.infer_expr(scope, &expr.children[0]);
+				let init = self.infer_expr(scope, &expr.children[1]);
+				self.widen_local(itervar, init.ty);
+				self.depend(itervar, scope, &expr.children[1]);
+				let mut loop_scope = scope.clone();
+				loop_scope.insert(itervar);
+				let body = self.infer_expr(&mut loop_scope, &expr.children[2]);
+				self.depend(itervar, &loop_scope, &expr.children[2]);
+				untyped(self.widen_local(itervar, body.ty))
+			}
+		}
+	}
+
+	fn next_tmp(&mut self) -> String {
+		let n = self.next_tmp;
+		self.next_tmp += 1;
+		format_tmp(n)
+	}
+
+	// give a value a temporary if it is not cheap to repeat
+	fn share(&mut self, value: Value) -> Result<Value, CompileError> {
+		if is_simple(&value.code) {
+			return Ok(value)
+		}
+		let tmp = self.next_tmp();
+		writeln!(self.buf, "let {tmp} = {};", value.code)?;
+		Ok(Value::new(tmp, value.ty))
+	}
+
+	// compile expressions whose values are used together. the code for an
+	// argument is only evaluated where it is used, so if a later argument
+	// assigns to a local the earlier ones are computed into temporaries first
+	fn compile_args(&mut self, local: &mut LocalState<'i>, exprs: &[Expression<'i>])
+	-> Result<Vec<Value>, CompileError> {
+		let mut args: Vec<Value> = Vec::with_capacity(exprs.len());
+		for expr in exprs {
+			let mark = self.buf.len();
+			let stores = self.stores;
+			let arg = self.compile_expr(local, expr)?;
+			if self.stores != stores {
+				let mut lets = String::new();
+				for prev in &mut args {
+					if is_simple(&prev.code) && !prev.code.starts_with("local_") {
+						continue
+					}
+					let tmp = self.next_tmp();
+					writeln!(lets, "let {tmp} = {};", prev.code)?;
+					*prev = Value::new(tmp, prev.ty);
+				}
+				self.buf.insert_str(mark, &lets);
+			}
+			args.push(arg);
+		}
+		Ok(args)
+	}
+
+	// compile the body of an if or loop, which is already in its own
+	// scope, without wrapping a block in another one
+	fn compile_body(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
+	-> Result<Value, CompileError> {
+		if !matches!(expr.ty, ExpressionType::Block) {
+			return self.compile_expr(local, expr)
+		}
+		let mut last = Value::new(String::new(), Type::Complex);
+		for child in &expr.children {
+			last = self.compile_expr(local, child)?;
+		}
+		Ok(last)
+	}
+
+	// compile a condition to a bool, skipping the
+	// conversion to a number for comparisons
+	fn compile_cond(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
 	-> Result<String, CompileError> {
+		let op = match expr.ty {
+			ExpressionType::Binary(BinaryOp::Gt) => ">",
+			ExpressionType::Binary(BinaryOp::Lt) => "<",
+			ExpressionType::Binary(BinaryOp::Ge) => ">=",
+			ExpressionType::Binary(BinaryOp::Le) => "<=",
+			_ => {
+				let cond = self.compile_expr(local, expr)?;
+				expect_number(&cond).map_err(|e| e.or_span(expr.span))?;
+				return Ok(format!("{} > 0.0", cond.real_part()))
+			}
+		};
+		let args = self.compile_args(local, &expr.children)?;
+		args.iter().try_for_each(expect_number).map_err(|e| e.or_span(expr.span))?;
+		Ok(format!("{} {op} {}", args[0].real_part(), args[1].real_part()))
+	}
+
+	fn compile_expr(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
+	-> Result<Value, CompileError> {
+		let outer_span = std::mem::replace(&mut self.buf.span, expr.span);
+		let value = self.compile_shared(local, expr)
+			.map_err(|e| e.or_span(expr.span));
+		self.buf.span = outer_span;
+		value
+	}
+
+	// compile an expression, reusing the temporary holding
+	// its value if it occurs more than once
+	fn compile_shared(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
+	-> Result<Value, CompileError> {
+		let repeated = self.subexpr_hashes.get(&(expr as *const _))
+			.and_then(|hash| self.subexpr_counts.get(hash))
+			.is_some_and(|n| *n > 1);
+		let value = self.compile_expr_inner(local, expr)?;
+		if !repeated {
+			return Ok(value)
+		}
+
+		if let Some((tmp, stores)) = local.shared.get(&value.code) {
+			if *stores == self.stores {
+				return Ok(tmp.clone())
+			}
+		}
+		let code = value.code.clone();
+		let tmp = self.share(value)?;
+		local.shared.insert(code, (tmp.clone(), self.stores));
+		Ok(tmp)
+	}
+
+	fn compile_expr_inner(&mut self, local: &mut LocalState<'i>, expr: &Expression<'i>)
+	-> Result<Value, CompileError> {
 		match expr.ty {
 			ExpressionType::Block => {
-				let tmp = local.next_tmp();
-				writeln!(self.buf, "var {tmp}: vec2f;")?;
+				let mark = self.buf.len();
 				writeln!(self.buf, "{{")?;
+				let body = self.buf.len();
 				let mut block_local = local.clone();
-				let mut last = String::new();
+				let mut last = Value::new(String::new(), Type::Complex);
 				for child in &expr.children {
 					last = self.compile_expr(&mut block_local, child)?;
 				}
-				writeln!(self.buf, "{tmp} = {last};")?;
+				if self.buf.len() == body {
+					// nothing in the block needs its own scope
+					self.buf.truncate(mark);
+					return Ok(last)
+				}
+				let tmp = self.next_tmp();
+				self.buf.insert_str(mark, &format!("var {tmp}: {};\n", last.ty.wgsl()));
+				self.with_span(expr.children.last(), |buf| writeln!(buf, "{tmp} = {};", last.code))?;
 				writeln!(self.buf, "}}")?;
-				Ok(tmp)
+				Ok(Value::new(tmp, last.ty))
 			}
-			ExpressionType::Name(v) => self.resolve_var(local, v),
+			ExpressionType::Name(v) => self.resolve_var(&local.local_vars, v),
 			ExpressionType::Store(var) => {
 				let a = self.compile_expr(local, &expr.children[0])?;
 				let name = format_local(var);
+				let ty = self.local_type(var);
 
+				if (a.ty == Type::Color) != (ty == Type::Color) {
+					return Err(format!("local {var} is given both colors and numbers").into())
+				}
 				if !local.local_vars.contains(var) {
 					write!(self.buf, "var ")?;
 					local.local_vars.insert(var);
 				}
 
-				writeln!(self.buf, "{name} = {a};")?;
-				Ok(name)
-			},
-			ExpressionType::Number(n) => {
-				let name = local.next_tmp();
-				writeln!(self.buf, "var {name} = vec2f({:?}, {:?});", n.re, n.im)?;
-				Ok(name)
+				writeln!(self.buf, "{name} = {};", a.to(ty))?;
+				self.stores += 1;
+				Ok(Value::new(name, ty))
 			},
+			ExpressionType::Number(n) => Ok(Value::number(n)),
 			ExpressionType::Binary(op) => {
-				let a = self.compile_expr(local, &expr.children[0])?;
-				let b = self.compile_expr(local, &expr.children[1])?;
-				let name = local.next_tmp();
-
-				match op {
-					BinaryOp::Add => writeln!(self.buf, "var {name} = {a} + {b};")?,
-					BinaryOp::Sub => writeln!(self.buf, "var {name} = {a} - {b};")?,
-					BinaryOp::Mul => writeln!(self.buf, "var {name} = c_mul({a}, {b});")?,
-					BinaryOp::Div => writeln!(self.buf, "var {name} = c_div({a}, {b});")?,
-					BinaryOp::Pow => writeln!(self.buf, "var {name} = c_pow({a}, {b});")?,
-					BinaryOp::Gt => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, {a}.x > {b}.x);")?,
-					BinaryOp::Lt => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, {a}.x < {b}.x);")?,
-					BinaryOp::Ge => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, {a}.x >= {b}.x);")?,
-					BinaryOp::Le => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, {a}.x <= {b}.x);")?,
-					BinaryOp::Eq => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, ({a}.x == {b}.x) && ({a}.y == {b}.y));")?,
-					BinaryOp::Ne => writeln!(self.buf, "var {name} = select(C_ZERO, C_ONE, ({a}.x != {b}.x) || ({a}.y != {b}.y));")?,
-				}
-
-				Ok(name)
+				let args = self.compile_args(local, &expr.children)?;
+				args.iter().try_for_each(expect_number)?;
+				Ok(types::binary(op, &args[0], &args[1]))
 			},
 			ExpressionType::Unary(op) => {
 				let a = self.compile_expr(local, &expr.children[0])?;
-				let name = local.next_tmp();
-
-				match op {
-					UnaryOp::Pos => writeln!(self.buf, "var {name} = {a};")?,
-					UnaryOp::Neg => writeln!(self.buf, "var {name} = -{a};")?,
-					UnaryOp::Conj => writeln!(self.buf, "var {name} = c_conj({a});")?,
-				}
-
-				Ok(name)
+				expect_number(&a)?;
+				Ok(types::unary(op, &a))
 			},
 			ExpressionType::FnCall(f) => {
-				let (fname, argc) = self.resolve_func(f)?;
-				if argc != expr.children.len() {
-					return Err(format!("function {f} expected {argc} args, got {}", expr.children.len()).into())
-				}
-
-				let mut args = Vec::with_capacity(expr.children.len());
-				for child in &expr.children {
-					args.push(self.compile_expr(local, child)?);
-				}
-
-				let name = local.next_tmp();
-				write!(self.buf, "var {name} = {fname}(", )?;
-				for arg in args {
-					write!(self.buf, "{arg}, ")?;
-				}
-				writeln!(self.buf, ");")?;
-
-				Ok(name)
+				self.check_arity(f, expr.children.len())?;
+				let args = self.compile_args(local, &expr.children)?;
+				self.call(f, args)
 			},
 			ExpressionType::If => {
-				let cond = self.compile_expr(local, &expr.children[0])?;
-				let result = local.next_tmp();
-				writeln!(self.buf, "var {result}: vec2f;")?;
-				writeln!(self.buf, "if {cond}.x > 0.0 {{")?;
-				let t = self.compile_expr(local, &expr.children[1])?;
-				writeln!(self.buf, "{result} = {t};")?;
+				let cond = self.compile_cond(local, &expr.children[0])?;
+				let result = self.next_tmp();
+				let mark = self.buf.len();
+				writeln!(self.buf, "if {cond} {{")?;
+				let t = self.compile_body(&mut local.clone(), &expr.children[1])?;
+				let t_mark = self.buf.len();
 				writeln!(self.buf, "}} else {{")?;
-				let f = self.compile_expr(local, &expr.children[2])?;
-				writeln!(self.buf, "{result} = {f};")?;
+				let f = self.compile_body(&mut local.clone(), &expr.children[2])?;
+				if (t.ty == Type::Color) != (f.ty == Type::Color) {
+					return Err("one branch of the if is a color and the other is a number".to_owned().into())
+				}
+				let ty = t.ty.join(f.ty);
+				writeln!(self.buf, "{result} = {};", f.to(ty))?;
 				writeln!(self.buf, "}}")?;
-				Ok(result)
+				self.buf.insert_str(t_mark, &format!("{result} = {};\n", t.to(ty)));
+				self.buf.insert_str(mark, &format!("var {result}: {};\n", ty.wgsl()));
+				Ok(Value::new(result, ty))
 			},
-            ExpressionType::While => {
-				let res = local.next_tmp();
-				writeln!(self.buf, "var {res}: vec2f;")?;
+			ExpressionType::While => {
+				let res = self.next_tmp();
+				let mark = self.buf.len();
 				writeln!(self.buf, "loop {{")?;
+				writeln!(self.buf, "if !use_iteration() {{ break; }}")?;
 
-                let cond = self.compile_expr(local, &expr.children[0])?;
-				writeln!(self.buf, "if {cond}.x <= 0.0 {{ break; }}")?;
+				let mut loop_local = local.loop_body();
+				let cond = self.compile_cond(&mut loop_local, &expr.children[0])?;
+				writeln!(self.buf, "if !({cond}) {{ break; }}")?;
 
-				let mut loop_local = local.clone();
-                let body = self.compile_expr(&mut loop_local, &expr.children[1])?;
-                writeln!(self.buf, "{res} = {body};")?;
+				let body = self.compile_body(&mut loop_local, &expr.children[1])?;
+				writeln!(self.buf, "{res} = {};", body.code)?;
 				writeln!(self.buf, "}}")?;
-				Ok(res)
-            }
+				self.buf.insert_str(mark, &format!("var {res}: {};\n", body.ty.wgsl()));
+				self.stores += 1;
+				Ok(Value::new(res, body.ty))
+			}
 			ExpressionType::Sum { countvar }
 			| ExpressionType::Prod { countvar } => {
-                let min = local.next_tmp();
-                let max = local.next_tmp();
+				let min = self.next_tmp();
+				let max = self.next_tmp();
+				self.check_loop_bound(local, &expr.children[0]);
+				self.check_loop_bound(local, &expr.children[1]);
 				let v = self.compile_expr(local, &expr.children[0])?;
-				writeln!(self.buf, "var {min} = i32(floor({v}.x));")?;
+				expect_number(&v)?;
+				writeln!(self.buf, "let {min} = i32(floor({}));", v.real_part())?;
 				let v = self.compile_expr(local, &expr.children[1])?;
-				writeln!(self.buf, "var {max} = i32(floor({v}.x));")?;
+				expect_number(&v)?;
+				writeln!(self.buf, "let {max} = i32(floor({}));", v.real_part())?;
 
-				let acc = local.next_tmp();
-				let ivar = local.next_tmp();
-				if matches!(expr.ty, ExpressionType::Sum { .. }) {
-					writeln!(self.buf, "var {acc} = vec2f(0.0, 0.0);")?;
-				} else {
-					writeln!(self.buf, "var {acc} = vec2f(1.0, 0.0);")?;
-				}
+				let acc = self.next_tmp();
+				let ivar = self.next_tmp();
+				let mark = self.buf.len();
 				writeln!(self.buf, "for(var {ivar} = {min}; {ivar} <= {max}; {ivar}++) {{")?;
-				writeln!(self.buf, "var {} = vec2f(f32({ivar}), 0.0);", format_local(countvar))?;
-				let mut loop_local = local.clone();
+				writeln!(self.buf, "if !use_iteration() {{ break; }}")?;
+				let count = Value::new(format!("f32({ivar})"), Type::Real);
+				writeln!(self.buf, "var {} = {};", format_local(countvar), count.to(self.local_type(countvar)))?;
+				let mut loop_local = local.loop_body();
 				loop_local.local_vars.insert(countvar);
-				let body = self.compile_expr(&mut loop_local, &expr.children[2])?;
-				if matches!(expr.ty, ExpressionType::Sum { .. }) {
-					writeln!(self.buf, "{acc} = {acc} + {body};")?;
+				let body = self.compile_body(&mut loop_local, &expr.children[2])?;
+				expect_number(&body)?;
+
+				let (init, op) = if matches!(expr.ty, ExpressionType::Sum { .. }) {
+					(0.0, BinaryOp::Add)
 				} else {
-					writeln!(self.buf, "{acc} = c_mul({acc}, {body});")?;
-				}
+					(1.0, BinaryOp::Mul)
+				};
+				let init = Value::number(init.into());
+				writeln!(self.buf, "{acc} = {};", types::binary(op, &Value::new(acc.clone(), body.ty), &body).code)?;
 				writeln!(self.buf, "}}")?;
-				Ok(acc)
+				self.buf.insert_str(mark, &format!("var {acc} = {};\n", init.to(body.ty)));
+				self.stores += 1;
+				Ok(Value::new(acc, body.ty))
 			},
 			ExpressionType::Iter { itervar } => {
-                let countvar = local.next_tmp();
+				let countvar = self.next_tmp();
+				self.check_loop_bound(local, &expr.children[0]);
 				let v = self.compile_expr(local, &expr.children[0])?;
-				writeln!(self.buf, "var {countvar} = i32(floor({v}.x));")?;
+				expect_number(&v)?;
+				writeln!(self.buf, "let {countvar} = i32(floor({}));", v.real_part())?;
 
 				let init = &expr.children[1];
 				let itervar_fmt = format_local(itervar);
+				let ty = self.local_type(itervar);
 				let v = self.compile_expr(local, init)?;
-				writeln!(self.buf, "var {itervar_fmt} = {v};")?;
+				writeln!(self.buf, "var {itervar_fmt} = {};", v.to(ty))?;
 
-				let ivar = local.next_tmp();
+				let ivar = self.next_tmp();
 				writeln!(self.buf, "for(var {ivar}: i32 = 0; {ivar} < {countvar}; {ivar}++) {{")?;
-				let mut loop_local = local.clone();
+				writeln!(self.buf, "if !use_iteration() {{ break; }}")?;
+				let mut loop_local = local.loop_body();
 				loop_local.local_vars.insert(itervar);
-				let body = self.compile_expr(&mut loop_local, &expr.children[2])?;
-				writeln!(self.buf, "{itervar_fmt} = {body};")?;
+				let body = self.compile_body(&mut loop_local, &expr.children[2])?;
+				if (body.ty == Type::Color) != (ty == Type::Color) {
+					return Err(format!("local {itervar} is given both colors and numbers").into())
+				}
+				writeln!(self.buf, "{itervar_fmt} = {};", body.to(ty))?;
 				writeln!(self.buf, "}}")?;
-				Ok(itervar_fmt)
+				self.stores += 1;
+				Ok(Value::new(itervar_fmt, ty))
+			}
+		}
+	}
+
+	fn local_type(&self, name: &str) -> Type {
+		self.local_types.get(name).copied().unwrap_or(Type::Complex)
+	}
+
+	fn check_arity(&self, f: &str, argc: usize) -> Result<(), CompileError> {
+		let arity = self.resolve_func(f)?.1;
+		let msg = match arity {
+			Arity::Fixed(n) if n != argc => format!("function {f} expected {n} args, got {argc}"),
+			Arity::Variadic(n) if n > argc => format!("function {f} expected at least {n} args, got {argc}"),
+			_ => return Ok(()),
+		};
+		let signature = match self.global_funcs.get(f) {
+			Some((args, _)) => format!("{f}({})", args.join(", ")),
+			None => builtin_signature(f, arity),
+		};
+		Err(format!("{msg}, signature is {signature}").into())
+	}
+
+	fn call(&self, f: &str, args: Vec<Value>) -> Result<Value, CompileError> {
+		self.check_arity(f, args.len())?;
+		args.iter().try_for_each(expect_number)?;
+		if let Some((_, ty)) = self.global_funcs.get(f) {
+			let args: Vec<String> = args.iter().map(Value::complex).collect();
+			return Ok(Value::new(format!("{}({})", format_func(f), args.join(", ")), *ty))
+		}
+		let (fname, arity) = self.resolve_func(f)?;
+		if let Arity::Variadic(_) = arity {
+			let mut args = args.into_iter();
+			let mut acc = args.next().ok_or_else(|| format!("function {f} expected at least 1 arg, got 0"))?;
+			for arg in args {
+				acc = types::call(&fname, &[acc, arg]);
 			}
+			return Ok(acc)
 		}
+		Ok(types::call(&fname, &args))
 	}
 
-	fn resolve_func(&self, name: &str) -> Result<(String, usize), CompileError> {
-		if let Some(argc) = self.global_funcs.get(name) {
-			Ok((format_func(name), *argc))
+	fn resolve_func(&self, name: &str) -> Result<(String, Arity), CompileError> {
+		if let Some((args, _)) = self.global_funcs.get(name) {
+			Ok((format_func(name), Arity::Fixed(args.len())))
 		} else if let Some((var, argc)) = BUILTIN_FUNCS.with(|c| c.get(name).copied()) {
 			Ok(((*var).to_owned(), argc))
 		} else {
-			Err(format!("use of undeclared function {name}").into())
+			let builtins: Vec<&str> = BUILTIN_FUNCS.with(|c| c.keys().copied().collect());
+			let suggestion = did_you_mean(name, self.global_funcs.keys().copied().chain(builtins));
+			Err(format!("use of undeclared function {name}{suggestion}").into())
 		}
 	}
 
-	fn resolve_var(&self, local: &LocalState, name: &str) -> Result<String, CompileError> {
-		if local.local_vars.contains(name) {
-			Ok(format_local(name))
-		} else if self.global_consts.contains(name) {
-			Ok(format_const(name) + "()")
+	fn resolve_var(&self, local_vars: &HashSet<&str>, name: &str) -> Result<Value, CompileError> {
+		if local_vars.contains(name) {
+			Ok(Value::new(format_local(name), self.local_type(name)))
+		} else if let Some(ty) = self.global_consts.get(name) {
+			if let Some(slot) = self.host_consts.get(name) {
+				Ok(Value::new(format_packed("uniforms.constants", *slot), Type::Complex))
+			} else {
+				Ok(Value::new(format_const(name) + "()", *ty))
+			}
 		} else if let Some(var) = self.vars.get(name) {
-			if var % 2 == 0 {
-				Ok(format!("uniforms.variables[{}].xy", var/2))
+			Ok(Value::new(format_packed("variables", *var), Type::Complex))
+		} else if let Some(u) = self.custom_uniforms.iter().find(|u| u.name == name) {
+			let ty = match u.value {
+				CustomValue::Real(_) => Type::Real,
+				CustomValue::Complex(..) => Type::Complex,
+			};
+			Ok(Value::new(format!("uniforms.{}", format_custom_uniform(name)), ty))
+		} else if let Some((code, ty)) = BUILTIN_UNIFORMS.with(|u| u.get(name).copied()) {
+			Ok(Value::new(code.to_owned(), ty))
+		} else if let Some((var, value)) = BUILTIN_CONSTS.with(|c| c.get(name).copied()) {
+			if value.im == 0.0 {
+				Ok(Value::number(value))
 			} else {
-				Ok(format!("uniforms.variables[{}].zw", var/2))
+				Ok(Value::new(var.to_owned(), Type::Complex))
 			}
-		} else if let Some(var) = BUILTIN_CONSTS.with(|c| Some(c.get(name)?.0)) {
-			Ok(var.to_owned())
 		} else {
-			Err(format!("use of undeclared variable {name}").into())
+			let builtins: Vec<&str> = BUILTIN_CONSTS.with(|c| c.keys().copied().collect());
+			let uniforms: Vec<&str> = BUILTIN_UNIFORMS.with(|u| u.keys().copied().collect());
+			let candidates = local_vars.iter().copied()
+				.chain(self.global_consts.keys().copied())
+				.chain(self.vars.keys().map(String::as_str))
+				.chain(self.custom_uniforms.iter().map(|u| u.name.as_str()))
+				.chain(uniforms)
+				.chain(builtins);
+			Err(format!("use of undeclared variable {name}{}", did_you_mean(name, candidates)).into())
 		}
 	}
 }
@@ -0,0 +1,961 @@
+use std::f64::consts::{E, PI, TAU, LN_2, LN_10};
+
+use num_complex::Complex64 as Complex;
+
+use super::ast::{BinaryOp, UnaryOp};
+
+// host implementations of the builtins in fragment.wgsl, keyed by
+// their wgsl names. these follow the shader code as closely as possible
+// so that folding an expression does not change its value (beyond the
+// extra precision). rand, gaussrand, noise, the colors, contour and isoline
+// have no host implementation and are left for the gpu.
+
+const C_ZERO: Complex = Complex::new(0.0, 0.0);
+const C_ONE: Complex = Complex::new(1.0, 0.0);
+const C_I: Complex = Complex::new(0.0, 1.0);
+
+const LOG_TAU: f64 = 1.8378770664093453;
+const EMGAMMA: f64 = 0.5772156649015329;
+
+// wgsl's sign() returns zero for zero
+fn sign(x: f64) -> f64 {
+	if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { x }
+}
+
+fn bool_cx(b: bool) -> Complex {
+	if b { C_ONE } else { C_ZERO }
+}
+
+fn real(x: f64) -> Complex {
+	Complex::new(x, 0.0)
+}
+
+fn map(z: Complex, f: impl Fn(f64) -> f64) -> Complex {
+	Complex::new(f(z.re), f(z.im))
+}
+
+fn fract(x: f64) -> f64 {
+	x - x.floor()
+}
+
+pub fn eval_builtin(func: &str, args: &[Complex]) -> Option<Complex> {
+	let a = |n: usize| args[n];
+	let result = match (func, args.len()) {
+		("c_pos", 1) => a(0),
+		("c_neg", 1) => -a(0),
+		("c_recip", 1) => c_recip(a(0)),
+		("c_conj", 1) => a(0).conj(),
+
+		("c_re", 1) => real(a(0).re),
+		("c_im", 1) => real(a(0).im),
+		("c_signre", 1) => real(sign(a(0).re)),
+		("c_signim", 1) => real(sign(a(0).im)),
+		("c_absre", 1) => real(a(0).re.abs()),
+		("c_absim", 1) => real(a(0).im.abs()),
+		("c_isnan", 1) => bool_cx(a(0).is_nan()),
+		("c_abs_sq", 1) => real(a(0).norm_sqr()),
+		("c_abs", 1) => real(a(0).norm()),
+		("c_arg", 1) => real(c_arg(a(0))),
+		("c_argbr", 2) => real(c_argbr(a(0), a(1))),
+		("c_diveu", 2) => c_diveu(a(0), a(1)),
+		("c_mod", 2) => a(0) - c_mul(c_diveu(a(0), a(1)), a(1)),
+		("c_floor", 1) => map(a(0), f64::floor),
+		("c_ceil", 1) => map(a(0), f64::ceil),
+		("c_round", 1) => map(a(0), f64::round_ties_even),
+
+		("c_add", 2) => a(0) + a(1),
+		("c_sub", 2) => a(0) - a(1),
+		("c_mul", 2) => c_mul(a(0), a(1)),
+		("c_div", 2) => c_div(a(0), a(1)),
+		("c_pow", 2) => c_pow(a(0), a(1)),
+		("c_powbr", 3) => c_powbr(a(0), a(1), a(2)),
+
+		("c_exp", 1) => c_exp(a(0)),
+		("c_log", 1) => c_log(a(0)),
+		("c_log2", 1) => c_log(a(0)) / LN_2,
+		("c_log10", 1) => c_log(a(0)) / LN_10,
+		("c_logb", 2) => c_div(c_log(a(1)), c_log(a(0))),
+		("c_logbr", 2) => c_logbr(a(0), a(1)),
+		("c_sqrt", 1) => c_pow(a(0), real(0.5)),
+		("c_sqrtbr", 2) => c_powbr(a(0), real(0.5), a(1)),
+		("c_cbrt", 1) => c_pow(a(0), real(1.0/3.0)),
+		("c_cbrtbr", 2) => c_powbr(a(0), real(1.0/3.0), a(1)),
+
+		("c_sin", 1) => c_sin(a(0)),
+		("c_cos", 1) => c_cos(a(0)),
+		("c_tan", 1) => c_tan(a(0)),
+		("c_sinh", 1) => c_sinh(a(0)),
+		("c_cosh", 1) => c_cosh(a(0)),
+		("c_tanh", 1) => c_tanh(a(0)),
+		("c_asin", 1) => c_asin(a(0)),
+		("c_acos", 1) => c_acos(a(0)),
+		("c_atan", 1) => c_atan(a(0)),
+		("c_asinh", 1) => c_asinh(a(0)),
+		("c_acosh", 1) => c_acosh(a(0)),
+		("c_atanh", 1) => 0.5 * (c_log(C_ONE + a(0)) - c_log(C_ONE - a(0))),
+
+		("c_gamma", 1) => c_exp(c_loggamma(a(0))),
+		("c_invgamma", 1) => c_exp(-c_loggamma(a(0))),
+		("c_loggamma", 1) => c_loggamma(a(0)),
+		("c_digamma", 1) => c_digamma(a(0)),
+		("c_beta", 2) => c_exp(c_loggamma(a(0)) + c_loggamma(a(1)) - c_loggamma(a(0) + a(1))),
+		("c_lambertw", 1) => c_lambertw(a(0)),
+		("c_lambertwbr", 2) => c_lambertwbr(a(0), a(1)),
+
+		("c_faddeeva", 1) => c_faddeeva(a(0)),
+		("c_erf", 1) => c_erf(a(0)),
+		("c_erfc", 1) => c_erfc(a(0)),
+		("c_erfi", 1) => c_erfi(a(0)),
+		("c_dawson", 1) => c_dawson(a(0)),
+		("c_fresnels", 1) => c_fresnels(a(0)),
+		("c_fresnelc", 1) => c_fresnelc(a(0)),
+
+		("c_gammainc", 2) => c_gammainc(a(0), a(1)),
+		("c_gammaincc", 2) => c_gammaincc(a(0), a(1)),
+		("c_e1", 1) => c_e1(a(0)),
+		("c_ei", 1) => c_ei(a(0)),
+		("c_li", 1) => c_li(a(0)),
+		("c_polylog", 2) => c_polylog(a(0), a(1)),
+
+		("c_mobius", 5) => c_div(c_mul(a(0), a(4)) + a(1), c_mul(a(2), a(4)) + a(3)),
+		("c_cayley", 1) => c_div(a(0) - C_I, a(0) + C_I),
+		("c_invcayley", 1) => c_div(c_mul(C_I, C_ONE + a(0)), C_ONE - a(0)),
+		("c_blaschke", 2) => c_div(a(1) - a(0), C_ONE - c_mul(a(0).conj(), a(1))),
+		("c_hdist", 2) => {
+			let r = (a(0) - a(1)).norm() / (C_ONE - c_mul(a(1).conj(), a(0))).norm();
+			real(2.0 * r.atanh())
+		}
+
+		("c_min", 2) => if a(1).re < a(0).re { a(1) } else { a(0) },
+		("c_max", 2) => if a(1).re > a(0).re { a(1) } else { a(0) },
+		("c_clamp", 3) => {
+			let z = if a(1).re > a(0).re { a(1) } else { a(0) };
+			if a(2).re < z.re { a(2) } else { z }
+		}
+		("c_atan2", 2) => real(atan2(a(0).re, a(1).re)),
+		("c_hypot", 2) => real((a(0).norm_sqr() + a(1).norm_sqr()).sqrt()),
+		("c_sign", 1) => map(a(0), sign),
+		("c_frac", 1) => map(a(0), fract),
+		("c_step", 2) => bool_cx(a(1).re >= a(0).re),
+		("c_smoothstep", 3) if a(0).re == a(1).re => bool_cx(a(2).re >= a(0).re),
+		("c_smoothstep", 3) => {
+			let t = ((a(2).re - a(0).re) / (a(1).re - a(0).re)).clamp(0.0, 1.0);
+			real(t * t * (3.0 - 2.0 * t))
+		}
+
+		("c_mix", 3) => c_mul(a(0), C_ONE - a(2)) + c_mul(a(1), a(2)),
+
+		_ => return None,
+	};
+	Some(result)
+}
+
+/// Whether `func` can be evaluated on the host with `argc` arguments
+pub fn has_host_impl(func: &str, argc: usize) -> bool {
+	eval_builtin(func, &vec![C_ZERO; argc]).is_some()
+}
+
+pub fn eval_binary(op: BinaryOp, a: Complex, b: Complex) -> Complex {
+	match op {
+		BinaryOp::Add => a + b,
+		BinaryOp::Sub => a - b,
+		BinaryOp::Mul => c_mul(a, b),
+		BinaryOp::Div => c_div(a, b),
+		BinaryOp::Pow => c_pow(a, b),
+		BinaryOp::Gt => bool_cx(a.re > b.re),
+		BinaryOp::Lt => bool_cx(a.re < b.re),
+		BinaryOp::Ge => bool_cx(a.re >= b.re),
+		BinaryOp::Le => bool_cx(a.re <= b.re),
+		BinaryOp::Eq => bool_cx(a == b),
+		BinaryOp::Ne => bool_cx(a != b),
+	}
+}
+
+pub fn eval_unary(op: UnaryOp, a: Complex) -> Complex {
+	match op {
+		UnaryOp::Pos => a,
+		UnaryOp::Neg => -a,
+		UnaryOp::Conj => a.conj(),
+	}
+}
+
+/// An expression that only depends on user variables and
+/// other host constants, evaluated on the host once per frame
+#[derive(Clone, Debug)]
+pub enum HostExpr {
+	Number(Complex),
+	Variable(usize),
+	Constant(usize),
+	/// A local of the constant being evaluated, by index
+	Local(usize),
+	Store(usize, Box<HostExpr>),
+	/// Expressions evaluated in order, giving the value of the last
+	Block(Vec<HostExpr>),
+	Binary(BinaryOp, Box<HostExpr>, Box<HostExpr>),
+	Unary(UnaryOp, Box<HostExpr>),
+	Call(&'static str, Vec<HostExpr>),
+}
+
+impl HostExpr {
+	fn eval(&self, variables: &[f32], constants: &[Complex], locals: &mut Vec<Complex>) -> Complex {
+		match self {
+			HostExpr::Number(n) => *n,
+			HostExpr::Variable(i) => Complex::new(variables[2*i] as f64, variables[2*i + 1] as f64),
+			HostExpr::Constant(i) => constants[*i],
+			HostExpr::Local(i) => locals[*i],
+			HostExpr::Store(i, a) => {
+				let v = a.eval(variables, constants, locals);
+				if *i >= locals.len() {
+					locals.resize(i + 1, C_ZERO);
+				}
+				locals[*i] = v;
+				v
+			}
+			HostExpr::Block(exprs) => exprs.iter().fold(C_ZERO, |_, a| a.eval(variables, constants, locals)),
+			HostExpr::Binary(op, a, b) => {
+				let a = a.eval(variables, constants, locals);
+				let b = b.eval(variables, constants, locals);
+				eval_binary(*op, a, b)
+			}
+			HostExpr::Unary(op, a) => eval_unary(*op, a.eval(variables, constants, locals)),
+			HostExpr::Call(func, args) => {
+				let args: Vec<Complex> = args.iter().map(|a| a.eval(variables, constants, locals)).collect();
+				eval_builtin(func, &args).expect("builtin has no host implementation")
+			}
+		}
+	}
+}
+
+/// Global constants hoisted out of the shader, in uniform slot order
+#[derive(Clone, Debug, Default)]
+pub struct HostConstants(pub Vec<HostExpr>);
+
+impl HostConstants {
+	/// Evaluate every constant from the packed user variables
+	/// and write the results to the packed constant slots
+	pub fn eval(&self, variables: &[f32], out: &mut [f32]) {
+		let mut values = Vec::with_capacity(self.0.len());
+		for (i, expr) in self.0.iter().enumerate() {
+			let v = expr.eval(variables, &values, &mut Vec::new());
+			out[2*i] = v.re as f32;
+			out[2*i + 1] = v.im as f32;
+			values.push(v);
+		}
+	}
+}
+
+fn c_mul(u: Complex, v: Complex) -> Complex {
+	Complex::new(u.re*v.re - u.im*v.im, u.im*v.re + u.re*v.im)
+}
+
+fn c_div(u: Complex, v: Complex) -> Complex {
+	Complex::new(u.re*v.re + u.im*v.im, u.im*v.re - u.re*v.im) / v.norm_sqr()
+}
+
+fn c_recip(v: Complex) -> Complex {
+	v.conj() / v.norm_sqr()
+}
+
+fn c_diveu(u: Complex, v: Complex) -> Complex {
+	map(c_div(u, v), f64::floor)
+}
+
+// the argument of zero is taken to be 0, and signed zeros are ignored
+fn c_arg(z: Complex) -> f64 {
+	if z.im == 0.0 {
+		return if z.re < 0.0 { PI } else { 0.0 }
+	}
+	z.im.atan2(z.re)
+}
+
+// atan2 as r_atan2 in the shader, which gives 0 at the origin
+fn atan2(y: f64, x: f64) -> f64 {
+	if y == 0.0 && x == 0.0 {
+		return 0.0
+	}
+	y.atan2(x)
+}
+
+fn c_argbr(z: Complex, br: Complex) -> f64 {
+	if z.re < 0.0 && z.im == 0.0 {
+		return PI + (br.re/TAU).floor() * TAU
+	}
+	if z == C_ZERO {
+		return br.re
+	}
+	let zr = c_mul(z, Complex::new((-br.re).cos(), (-br.re).sin()));
+	br.re + zr.im.atan2(zr.re)
+}
+
+fn c_exp(z: Complex) -> Complex {
+	z.re.exp() * Complex::new(z.im.cos(), z.im.sin())
+}
+
+fn c_log(z: Complex) -> Complex {
+	Complex::new(0.5 * z.norm_sqr().ln(), c_arg(z))
+}
+
+fn c_logbr(z: Complex, br: Complex) -> Complex {
+	Complex::new(0.5 * z.norm_sqr().ln(), c_argbr(z, br))
+}
+
+// integer powers are taken by repeated multiplication, as r_powi does in
+// the shader, so that powers of integers fold to exact integers
+fn c_pow(u: Complex, v: Complex) -> Complex {
+	if v.im == 0.0 && v.re.fract() == 0.0 && v.re.abs() <= 64.0 {
+		return c_powi(u, v.re as i32)
+	}
+	if u == C_ZERO && v.re >= 0.0 {
+		return c_pow_zero(v)
+	}
+	c_exp(c_mul(c_log(u), v))
+}
+
+// zero to a power, where the logarithm is infinite
+fn c_pow_zero(v: Complex) -> Complex {
+	if v == C_ZERO { C_ONE } else { C_ZERO }
+}
+
+fn c_powi(u: Complex, n: i32) -> Complex {
+	let mut base = if n < 0 { c_recip(u) } else { u };
+	let mut k = n.unsigned_abs();
+	let mut result = C_ONE;
+	while k != 0 {
+		if k & 1 != 0 {
+			result = c_mul(result, base);
+		}
+		base = c_mul(base, base);
+		k >>= 1;
+	}
+	result
+}
+
+fn c_powbr(u: Complex, v: Complex, br: Complex) -> Complex {
+	if u == C_ZERO && v.re >= 0.0 {
+		return c_pow_zero(v)
+	}
+	c_exp(c_mul(c_logbr(u, br), v))
+}
+
+fn c_sin(z: Complex) -> Complex {
+	Complex::new(z.re.sin()*z.im.cosh(), z.re.cos()*z.im.sinh())
+}
+
+fn c_cos(z: Complex) -> Complex {
+	Complex::new(z.re.cos()*z.im.cosh(), -z.re.sin()*z.im.sinh())
+}
+
+fn c_tan(z: Complex) -> Complex {
+	Complex::new((2.0*z.re).sin(), (2.0*z.im).sinh()) / ((2.0*z.re).cos() + (2.0*z.im).cosh())
+}
+
+fn c_sinh(z: Complex) -> Complex {
+	Complex::new(z.re.sinh()*z.im.cos(), z.re.cosh()*z.im.sin())
+}
+
+fn c_cosh(z: Complex) -> Complex {
+	Complex::new(z.re.cosh()*z.im.cos(), z.re.sinh()*z.im.sin())
+}
+
+fn c_tanh(z: Complex) -> Complex {
+	Complex::new((2.0*z.re).sinh(), (2.0*z.im).sin()) / ((2.0*z.re).cosh() + (2.0*z.im).cos())
+}
+
+fn c_asin(z: Complex) -> Complex {
+	let m = if z.im < 0.0 || (z.im == 0.0 && z.re > 0.0) { 1.0 } else { -1.0 };
+	let u = c_pow(C_ONE - c_mul(z, z), real(0.5));
+	let v = c_log(u + m*Complex::new(-z.im, z.re));
+	m*Complex::new(v.im, -v.re)
+}
+
+fn c_acos(z: Complex) -> Complex {
+	let m = if z.im < 0.0 || (z.im == 0.0 && z.re > 0.0) { 1.0 } else { -1.0 };
+	let u = c_pow(C_ONE - c_mul(z, z), real(0.5));
+	let v = c_log(u + m*Complex::new(-z.im, z.re));
+	real(PI/2.0) + m*Complex::new(-v.im, v.re)
+}
+
+fn c_atan(z: Complex) -> Complex {
+	let u = C_ONE - Complex::new(-z.im, z.re);
+	let v = C_ONE + Complex::new(-z.im, z.re);
+	let w = c_log(c_div(u, v));
+	0.5 * Complex::new(-w.im, w.re)
+}
+
+fn c_asinh(z: Complex) -> Complex {
+	let m = if z.re > 0.0 || (z.re == 0.0 && z.im > 0.0) { 1.0 } else { -1.0 };
+	let u = c_pow(C_ONE + c_mul(z, z), real(0.5));
+	c_log(u + z*m) * m
+}
+
+fn c_acosh(z: Complex) -> Complex {
+	let b = if z.re < 0.0 || (z.re == 0.0 && z.im < 0.0) { TAU } else { 0.0 };
+	let u = c_powbr(-C_ONE + c_mul(z, z), real(0.5), real(b));
+	c_log(u + z)
+}
+
+fn c_loggamma(z: Complex) -> Complex {
+	let reflect = z.re < 0.5 && z.im.abs() < 13.0;
+	let zp = if reflect { C_ONE - z } else { z };
+	let mut w = c_loggamma_inner2(zp);
+	if reflect {
+		let br = PI * (0.5 - z.re) * sign(z.im);
+		w = real(LOG_TAU - LN_2) - c_logbr(c_sin(PI * z), real(br)) - w;
+	}
+	w
+}
+
+// the shader shifts by 3 to stay accurate in f32, shifting
+// further here costs nothing and gains the extra precision
+fn c_loggamma_inner(z: Complex) -> Complex {
+	let zr = c_recip(z);
+	let zr2 = c_mul(zr, zr);
+	let s = c_mul(zr, real(1.0/12.0) - c_mul(zr2, real(1.0/360.0) - c_mul(zr2, real(1.0/1260.0) - zr2/1680.0)));
+	c_mul(z - real(0.5), c_log(z)) - z + real(0.5*LOG_TAU) + s
+}
+
+fn c_loggamma_inner2(z: Complex) -> Complex {
+	let mut l = C_ZERO;
+	for k in 0..8 {
+		l += c_log(z + real(k as f64));
+	}
+	c_loggamma_inner(z + real(8.0)) - l
+}
+
+fn c_digamma(z: Complex) -> Complex {
+	let reflect = z.re < 0.5 && z.im.abs() < 13.0;
+	let zp = if reflect { C_ONE - z } else { z };
+	let mut w = c_digamma_inner2(zp);
+	if reflect {
+		w -= PI * c_recip(c_tan(PI * z));
+	}
+	w
+}
+
+fn c_digamma_inner(z: Complex) -> Complex {
+	let zr = c_recip(z);
+	let zr2 = c_mul(zr, zr);
+	let zr4 = c_mul(zr2, zr2);
+	let zr6 = c_mul(zr2, zr4);
+	let zr8 = c_mul(zr4, zr4);
+	c_log(z) - 0.5*zr - zr2/12.0 + zr4/120.0 - zr6/252.0 + zr8/240.0
+}
+
+fn c_digamma_inner2(z: Complex) -> Complex {
+	let mut l = C_ZERO;
+	for k in 0..8 {
+		l += c_recip(z + real(k as f64));
+	}
+	c_digamma_inner(z + real(8.0)) - l
+}
+
+fn c_lambertw(z: Complex) -> Complex {
+	c_lambertw_iter(z, c_lambertw_init(z, 0.0))
+}
+
+fn c_lambertwbr(z: Complex, br: Complex) -> Complex {
+	let br_n = br.re / TAU;
+	let branch_point = if br.re.abs() < PI { real(-1.0 / E) } else { C_ZERO };
+	let arg = c_arg(z - branch_point);
+	let take_ceil = br_n - br_n.floor() >= arg / TAU + 0.5;
+	let init_br = if take_ceil { br_n.ceil() } else { br_n.floor() };
+	c_lambertw_iter(z, c_lambertw_init(z, init_br))
+}
+
+// newton's method, with more iterations than the shader for the extra precision
+fn c_lambertw_iter(z: Complex, init: Complex) -> Complex {
+	let mut w = init;
+	for _ in 0..8 {
+		w = c_div(c_mul(w, w) + c_mul(z, c_exp(-w)), w + C_ONE);
+	}
+	w
+}
+
+fn c_lambertw_init(z: Complex, br: f64) -> Complex {
+	let b = real(TAU * br);
+	let oz = z + real(1.25);
+	if br == 0.0 && z.norm_sqr() <= 50.0
+	|| br == 1.0 && z.im < 0.0 && oz.norm_sqr() < 1.0
+	|| br == -1.0 && z.im > 0.0 && oz.norm_sqr() < 1.0 {
+		let w = C_ONE + c_powbr(C_ONE + E*z, real(0.5), b);
+		c_div(c_mul(E*z, c_log(w)), w + E*z)
+	} else {
+		let logz = c_logbr(z, b);
+		logz - c_log(logz)
+	}
+}
+
+const FADDEEVA_L: f64 = 4.119534287814236;
+const FADDEEVA_COEFFS: [f64; 24] = [
+	-1.513746129e-10, 4.904820490e-09, 1.331045442e-09, -3.008282348e-08,
+	-1.912225889e-08, 1.873834345e-07, 2.568264134e-07, -1.085647580e-06,
+	-3.038893184e-06, 4.139461724e-06, 3.047106608e-05, 2.433141546e-05,
+	-2.074843151e-04, -7.816642996e-04, -4.936426901e-04, 6.215006363e-03,
+	3.372336686e-02, 1.083872348e-01, 2.654963960e-01, 5.361139536e-01,
+	9.257087139e-01, 1.394819673e+00, 1.856286499e+00, 2.197858937e+00,
+];
+
+fn c_faddeeva_upper(z: Complex) -> Complex {
+	let iz = Complex::new(-z.im, z.re);
+	let r = c_recip(real(FADDEEVA_L) - iz);
+	let zz = c_mul(real(FADDEEVA_L) + iz, r);
+	let p = FADDEEVA_COEFFS.iter().fold(C_ZERO, |p, c| c_mul(p, zz) + c);
+	2.0 * c_mul(p, c_mul(r, r)) + r / PI.sqrt()
+}
+
+fn c_faddeeva(z: Complex) -> Complex {
+	if z.im >= 0.0 {
+		c_faddeeva_upper(z)
+	} else {
+		2.0 * c_exp(-c_mul(z, z)) - c_faddeeva_upper(-z)
+	}
+}
+
+fn c_erfc_plus(z: Complex) -> Complex {
+	c_mul(c_exp(-c_mul(z, z)), c_faddeeva_upper(Complex::new(-z.im, z.re)))
+}
+
+fn c_erfc(z: Complex) -> Complex {
+	if z.re >= 0.0 {
+		c_erfc_plus(z)
+	} else {
+		2.0 * C_ONE - c_erfc_plus(-z)
+	}
+}
+
+fn c_erf(z: Complex) -> Complex {
+	if z.norm_sqr() < 0.25 {
+		let z2 = -c_mul(z, z);
+		let mut term = z;
+		let mut sum = z;
+		for n in 1..14 {
+			let nf = n as f64;
+			term = c_mul(term, z2) / nf;
+			sum += term / (2.0 * nf + 1.0);
+		}
+		return 2.0 / PI.sqrt() * sum
+	}
+	if z.re >= 0.0 {
+		C_ONE - c_erfc_plus(z)
+	} else {
+		c_erfc_plus(-z) - C_ONE
+	}
+}
+
+fn c_erfi(z: Complex) -> Complex {
+	let w = c_erf(Complex::new(-z.im, z.re));
+	Complex::new(w.im, -w.re)
+}
+
+fn c_dawson(z: Complex) -> Complex {
+	if z.norm_sqr() < 0.25 {
+		let z2 = -2.0 * c_mul(z, z);
+		let mut term = z;
+		let mut sum = z;
+		for n in 1..14 {
+			term = c_mul(term, z2) / (2.0 * n as f64 + 1.0);
+			sum += term;
+		}
+		return sum
+	}
+	let m = if z.im < 0.0 { -1.0 } else { 1.0 };
+	let zp = m * z;
+	let w = c_exp(-c_mul(zp, zp)) - c_faddeeva_upper(zp);
+	m * 0.5 * PI.sqrt() * Complex::new(-w.im, w.re)
+}
+
+fn c_fresnels(z: Complex) -> Complex {
+	if z.norm_sqr() < 1.0 {
+		let z4 = c_mul(c_mul(z, z), c_mul(z, z));
+		let mut term = PI / 2.0 * c_mul(z, c_mul(z, z));
+		let mut sum = term / 3.0;
+		for n in 1..12 {
+			let nf = n as f64;
+			term = -c_mul(term, z4) * (PI * PI / 4.0) / ((2.0 * nf) * (2.0 * nf + 1.0));
+			sum += term / (4.0 * nf + 3.0);
+		}
+		return sum
+	}
+	let u = c_erf(0.5 * PI.sqrt() * c_mul(Complex::new(1.0, 1.0), z));
+	let v = c_erf(0.5 * PI.sqrt() * c_mul(Complex::new(1.0, -1.0), z));
+	0.25 * c_mul(Complex::new(1.0, 1.0), u - Complex::new(-v.im, v.re))
+}
+
+fn c_fresnelc(z: Complex) -> Complex {
+	if z.norm_sqr() < 1.0 {
+		let z4 = c_mul(c_mul(z, z), c_mul(z, z));
+		let mut term = z;
+		let mut sum = z;
+		for n in 1..12 {
+			let nf = n as f64;
+			term = -c_mul(term, z4) * (PI * PI / 4.0) / ((2.0 * nf - 1.0) * (2.0 * nf));
+			sum += term / (4.0 * nf + 1.0);
+		}
+		return sum
+	}
+	let u = c_erf(0.5 * PI.sqrt() * c_mul(Complex::new(1.0, 1.0), z));
+	let v = c_erf(0.5 * PI.sqrt() * c_mul(Complex::new(1.0, -1.0), z));
+	0.25 * c_mul(Complex::new(1.0, -1.0), u + Complex::new(-v.im, v.re))
+}
+
+fn c_gammainc(a: Complex, z: Complex) -> Complex {
+	if z.re < a.re + 1.0 {
+		c_gammainc_series(a, z)
+	} else {
+		C_ONE - c_gammaincc_cf(a, z)
+	}
+}
+
+fn c_gammaincc(a: Complex, z: Complex) -> Complex {
+	if z.re < a.re + 1.0 {
+		C_ONE - c_gammainc_series(a, z)
+	} else {
+		c_gammaincc_cf(a, z)
+	}
+}
+
+fn c_gammainc_prefactor(a: Complex, z: Complex) -> Complex {
+	if z == C_ZERO && a.re > 0.0 {
+		return C_ZERO
+	}
+	c_exp(c_mul(a, c_log(z)) - z - c_loggamma(a))
+}
+
+fn c_gammainc_series(a: Complex, z: Complex) -> Complex {
+	let mut ap = a;
+	let mut term = c_recip(a);
+	let mut sum = term;
+	for _ in 0..400 {
+		ap += C_ONE;
+		term = c_div(c_mul(term, z), ap);
+		sum += term;
+		if term.norm_sqr() < 1.0e-32 * sum.norm_sqr() {
+			break
+		}
+	}
+	c_mul(sum, c_gammainc_prefactor(a, z))
+}
+
+fn c_gammaincc_cf(a: Complex, z: Complex) -> Complex {
+	c_mul(c_gammaincc_lentz(a, z), c_gammainc_prefactor(a, z))
+}
+
+fn c_gammaincc_lentz(a: Complex, z: Complex) -> Complex {
+	let mut b = z + C_ONE - a;
+	let mut c = real(1.0e300);
+	let mut d = c_recip(b);
+	let mut h = d;
+	for k in 1..400 {
+		let fk = k as f64;
+		let an = -fk * (real(fk) - a);
+		b += real(2.0);
+		d = c_mul(an, d) + b;
+		if d.norm_sqr() < 1.0e-300 {
+			d = real(1.0e-150);
+		}
+		c = b + c_div(an, c);
+		if c.norm_sqr() < 1.0e-300 {
+			c = real(1.0e-150);
+		}
+		d = c_recip(d);
+		let del = c_mul(c, d);
+		h = c_mul(h, del);
+		if (del - C_ONE).norm_sqr() < 1.0e-30 {
+			break
+		}
+	}
+	h
+}
+
+fn c_e1(z: Complex) -> Complex {
+	if c_expint_use_cf(z) {
+		return c_mul(c_gammaincc_lentz(C_ZERO, z), c_exp(-z))
+	}
+	let mut term = -z;
+	let mut sum = term;
+	for k in 2..400 {
+		let fk = k as f64;
+		term = c_mul(term, -z) * ((fk - 1.0) / (fk * fk));
+		sum += term;
+		if term.norm_sqr() < 1.0e-32 * sum.norm_sqr() {
+			break
+		}
+	}
+	-real(EMGAMMA) - c_log(z) - sum
+}
+
+fn c_ei(z: Complex) -> Complex {
+	if c_expint_use_cf(-z) {
+		return -c_e1(-z) + Complex::new(0.0, PI * sign(z.im))
+	}
+	let mut term = z;
+	let mut sum = term;
+	for k in 2..400 {
+		let fk = k as f64;
+		term = c_mul(term, z) * ((fk - 1.0) / (fk * fk));
+		sum += term;
+		if term.norm_sqr() < 1.0e-32 * sum.norm_sqr() {
+			break
+		}
+	}
+	let mut l = c_log(z);
+	if z.im == 0.0 {
+		l.im = 0.0;
+	}
+	real(EMGAMMA) + l + sum
+}
+
+fn c_li(z: Complex) -> Complex {
+	if z == C_ZERO {
+		return C_ZERO
+	}
+	c_ei(c_log(z))
+}
+
+fn c_expint_use_cf(z: Complex) -> bool {
+	z.norm_sqr() >= 4.0 && (z.re >= 0.0 || z.im.abs() >= 2.0)
+}
+
+const ZETA_SMALL: [f64; 6] = [
+	1.6449340668482264, 1.2020569031595942, 1.0823232337111381,
+	1.03692775514337, 1.017343061984449, 1.0083492773819228,
+];
+
+fn zeta_int(n: i32) -> f64 {
+	if n < 8 {
+		return ZETA_SMALL[n as usize - 2]
+	}
+	(1..32).map(|k| (k as f64).powi(-n)).sum()
+}
+
+const HURWITZ_COEFFS: [f64; 6] = [
+	1.0/12.0, -1.0/720.0, 1.0/30240.0,
+	-1.0/1209600.0, 1.0/47900160.0, -5.2841901e-10,
+];
+
+fn c_hurwitz_zeta(s: Complex, a: Complex) -> Complex {
+	let n = (s.norm().ceil() as i32).clamp(2, 16);
+	let mut sum = C_ZERO;
+	for k in 0..n {
+		sum += c_pow(a + real(k as f64), -s);
+	}
+	let w = a + real(n as f64);
+	let wr2 = c_recip(c_mul(w, w));
+	let p = c_pow(w, -s);
+	sum += c_div(c_mul(w, p), s - C_ONE) + 0.5 * p;
+	let mut t = c_div(c_mul(s, p), w);
+	for (j, coeff) in HURWITZ_COEFFS.iter().enumerate() {
+		sum += coeff * t;
+		let fj = (2 * j) as f64;
+		t = c_mul(t, c_mul(c_mul(s + real(fj + 1.0), s + real(fj + 2.0)), wr2));
+	}
+	sum
+}
+
+fn c_zeta(s: Complex) -> Complex {
+	if s.re >= 0.5 {
+		return c_hurwitz_zeta(s, C_ONE)
+	}
+	let sp = C_ONE - s;
+	let f = c_exp(LN_2 * s - PI.ln() * sp + c_loggamma(sp));
+	c_mul(c_mul(f, c_sin(PI / 2.0 * s)), c_hurwitz_zeta(sp, C_ONE))
+}
+
+fn c_polylog(s: Complex, z: Complex) -> Complex {
+	if z.norm_sqr() < 0.5625 {
+		return c_polylog_series(s, z)
+	}
+	let n = s.re.round_ties_even();
+	let eps = s - real(n);
+	// at z = 1 the series for non-integer s are degenerate
+	if z == C_ONE && s.re > 1.0 && eps != C_ZERO {
+		return c_zeta(s)
+	}
+	if n == 0.0 && eps == C_ZERO {
+		return c_div(z, C_ONE - z)
+	}
+	if n < 1.0 || eps.norm_sqr() >= 0.01 {
+		return c_polylog_nonint(s, z)
+	}
+	let l0 = c_polylog_int(n as i32, z);
+	if eps == C_ZERO {
+		return l0
+	}
+	let t = eps.norm() / 0.1;
+	let d = eps / t;
+	let lp = c_polylog_nonint(real(n) + d, z);
+	let lm = c_polylog_nonint(real(n) - d, z);
+	l0 + 0.5 * t * (lp - lm) + 0.5 * t * t * (lp - 2.0 * l0 + lm)
+}
+
+fn c_polylog_int(n: i32, z: Complex) -> Complex {
+	if n == 1 {
+		return -c_log(C_ONE - z)
+	}
+	if z.norm_sqr() > 1.7777778 {
+		c_polylog_inversion(n, z)
+	} else {
+		c_polylog_logseries(n, z)
+	}
+}
+
+fn c_polylog_nonint(s: Complex, z: Complex) -> Complex {
+	let mu = c_log(z);
+	if mu.norm_sqr() < 16.0 {
+		return c_polylog_logseries_cx(s, mu)
+	}
+	c_polylog_jonquiere(s, z)
+}
+
+fn c_polylog_series(s: Complex, z: Complex) -> Complex {
+	let mut zk = z;
+	let mut sum = z;
+	for k in 2..120 {
+		zk = c_mul(zk, z);
+		sum += c_mul(zk, c_exp(-s * (k as f64).ln()));
+	}
+	sum
+}
+
+fn c_polylog_logseries(n: i32, z: Complex) -> Complex {
+	if z == C_ONE {
+		return real(zeta_int(n))
+	}
+	let mu = c_log(z);
+	let mut sum = C_ZERO;
+	let mut t = C_ONE;
+	let mut harmonic = 0.0;
+	for k in 0..n - 1 {
+		sum += zeta_int(n - k) * t;
+		t = c_mul(t, mu) / (k + 1) as f64;
+		harmonic += 1.0 / (k + 1) as f64;
+	}
+	sum += c_mul(t, real(harmonic) - c_log(-mu));
+	t = c_mul(t, mu) / n as f64;
+	sum -= 0.5 * t;
+	let mut c = -2.0 / (TAU * TAU);
+	for j in 1..13 {
+		let k = n + 2 * j - 1;
+		t = c_mul(t, mu) / k as f64;
+		sum += c * zeta_int(2 * j) * t;
+		t = c_mul(t, mu) / (k + 1) as f64;
+		c *= -((2 * j * (2 * j + 1)) as f64) / (TAU * TAU);
+	}
+	sum
+}
+
+fn c_polylog_logseries_cx(s: Complex, mu: Complex) -> Complex {
+	let mut sum = c_exp(c_loggamma(C_ONE - s) + c_mul(s - C_ONE, c_log(-mu)));
+	let mut t = C_ONE;
+	for k in 0..32 {
+		sum += c_mul(c_zeta(s - real(k as f64)), t);
+		t = c_mul(t, mu) / (k + 1) as f64;
+	}
+	sum
+}
+
+fn c_polylog_inversion(n: i32, z: Complex) -> Complex {
+	let y = c_log(-z) + Complex::new(0.0, PI);
+	let mut sum = C_ZERO;
+	let mut e = C_ONE;
+	for m in 0..=n {
+		let k = n - m;
+		if k == 0 {
+			sum += e;
+		} else if k == 1 {
+			sum += c_mul(Complex::new(0.0, -PI), e);
+		} else if k % 2 == 0 {
+			sum -= 2.0 * zeta_int(k) * e;
+		}
+		e = c_mul(e, y) / (m + 1) as f64;
+	}
+	let sgn = if n % 2 == 0 { -1.0 } else { 1.0 };
+	sgn * c_polylog_series(real(n as f64), c_recip(z)) - sum
+}
+
+fn c_polylog_jonquiere(s: Complex, z: Complex) -> Complex {
+	let l = c_log(-z) / TAU;
+	let a1 = Complex::new(0.5 + l.im, -l.re);
+	let a2 = Complex::new(0.5 - l.im, l.re);
+	let sigma = C_ONE - s;
+	let ip = c_exp(c_mul(Complex::new(0.0, PI / 2.0), sigma));
+	let f = c_exp(c_loggamma(sigma) - LOG_TAU * sigma);
+	let h = c_mul(ip, c_hurwitz_zeta(sigma, a1)) + c_mul(c_recip(ip), c_hurwitz_zeta(sigma, a2));
+	c_mul(f, h)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn eval(func: &str, args: &[Complex]) -> Complex {
+		eval_builtin(func, args).unwrap()
+	}
+
+	fn assert_close(func: &str, args: &[Complex], expected: Complex) {
+		let z = eval(func, args);
+		assert!((z - expected).norm() <= 1e-9 * expected.norm().max(1.0),
+			"{func}{args:?} = {z}, expected {expected}");
+	}
+
+	#[test]
+	fn powers_of_zero() {
+		assert_eq!(eval("c_pow", &[C_ZERO, C_ZERO]), C_ONE);
+		assert_eq!(eval("c_pow", &[C_ZERO, real(2.5)]), C_ZERO);
+		assert_eq!(eval("c_pow", &[C_ZERO, Complex::new(0.5, 3.0)]), C_ZERO);
+		assert_eq!(eval("c_powbr", &[C_ZERO, real(1.0), real(2.0)]), C_ZERO);
+		assert_eq!(eval("c_sqrt", &[C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_sqrtbr", &[C_ZERO, real(1.0)]), C_ZERO);
+		assert_eq!(eval("c_cbrt", &[C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_cbrtbr", &[C_ZERO, real(-1.0)]), C_ZERO);
+	}
+
+	#[test]
+	fn arguments_at_zero() {
+		assert_eq!(eval("c_arg", &[C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_arg", &[-C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_argbr", &[C_ZERO, real(2.5)]), real(2.5));
+		assert_eq!(eval("c_atan2", &[C_ZERO, C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_log", &[C_ZERO]), real(f64::NEG_INFINITY));
+		assert_eq!(eval("c_logbr", &[C_ZERO, real(1.0)]), Complex::new(f64::NEG_INFINITY, 1.0));
+	}
+
+	#[test]
+	fn special_functions_at_zero() {
+		assert_eq!(eval("c_gammainc", &[real(1.0), C_ZERO]), C_ZERO);
+		assert_eq!(eval("c_gammainc", &[real(0.5), C_ZERO]), C_ZERO);
+		assert_close("c_gammaincc", &[real(1.0), C_ZERO], C_ONE);
+		assert_close("c_gammaincc", &[real(0.5), C_ZERO], C_ONE);
+		assert_eq!(eval("c_li", &[C_ZERO]), C_ZERO);
+		assert!(eval("c_gamma", &[C_ZERO]).re.is_infinite());
+		assert!(eval("c_beta", &[C_ZERO, real(1.0)]).re.is_infinite());
+		assert_eq!(eval("c_invgamma", &[C_ZERO]), C_ZERO);
+	}
+
+	#[test]
+	fn smoothstep_with_equal_edges() {
+		assert_eq!(eval("c_smoothstep", &[real(1.0), real(1.0), real(0.5)]), C_ZERO);
+		assert_eq!(eval("c_smoothstep", &[real(1.0), real(1.0), real(1.0)]), C_ONE);
+		assert_eq!(eval("c_smoothstep", &[real(0.0), real(2.0), real(1.0)]), real(0.5));
+	}
+
+	#[test]
+	fn known_values() {
+		assert_close("c_sqrt", &[real(-4.0)], Complex::new(0.0, 2.0));
+		assert_close("c_arg", &[real(-1.0)], real(PI));
+		assert_close("c_asin", &[real(1.0)], real(PI / 2.0));
+		assert_close("c_acos", &[real(-1.0)], real(PI));
+		assert_close("c_acosh", &[real(1.0)], C_ZERO);
+		assert_close("c_gamma", &[real(5.0)], real(24.0));
+		assert_close("c_gamma", &[real(0.5)], real(PI.sqrt()));
+		assert_close("c_beta", &[real(2.0), real(3.0)], real(1.0 / 12.0));
+		assert_close("c_gammainc", &[real(1.0), real(2.0)], real(1.0 - (-2.0f64).exp()));
+		assert_close("c_gammaincc", &[real(1.0), real(2.0)], real((-2.0f64).exp()));
+		assert_close("c_erf", &[real(1.0)], real(0.8427007929497149));
+		assert_close("c_li", &[real(2.0)], real(1.045163780117493));
+		assert_close("c_polylog", &[real(2.0), real(1.0)], real(PI * PI / 6.0));
+		assert_close("c_polylog", &[real(2.5), real(1.0)], real(1.341_487_257_250_917));
+		assert_close("c_polylog", &[real(3.3), real(1.0)], real(1.151_944_794_720_774));
+		assert_close("c_polylog", &[Complex::new(2.0, 1.0), real(1.0)], Complex::new(1.150_355_703_254_903, -0.437_530_865_919_608));
+	}
+}
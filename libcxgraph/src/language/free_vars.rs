@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use super::{ast::{Definition, Expression, ExpressionType, Param, Span}, builtins::{BUILTIN_CONSTS, BUILTIN_UNIFORMS}};
+
+// scoping here mirrors infer_expr in the compiler, except that every
+// global is treated as declared, wherever in the program it is defined
+
+/// Names used as variables that are not builtins, custom uniforms, parameters, globals
+/// or locals in scope, in order of their first use, with the span of that use
+pub fn find_free_variables<'i>(params: &[Param<'i>], defns: &[Definition<'i>], custom_uniforms: &HashSet<&str>)
+-> Vec<(&'i str, Span)> {
+	let mut finder = Finder {
+		custom_uniforms,
+		globals: params.iter().map(|param| param.name)
+			.chain(defns.iter().map(Definition::name))
+			.collect(),
+		seen: HashSet::new(),
+		free: Vec::new(),
+	};
+	for defn in defns {
+		let mut scope: HashSet<&str> = match defn {
+			Definition::Constant { .. } => HashSet::new(),
+			Definition::Function { args, .. } => args.iter().copied().collect(),
+		};
+		for expr in defn.value() {
+			finder.walk(&mut scope, expr);
+		}
+	}
+	finder.free
+}
+
+struct Finder<'c, 'i> {
+	custom_uniforms: &'c HashSet<&'c str>,
+	globals: HashSet<&'i str>,
+	seen: HashSet<&'i str>,
+	free: Vec<(&'i str, Span)>,
+}
+
+impl<'c, 'i> Finder<'c, 'i> {
+	fn walk(&mut self, scope: &mut HashSet<&'i str>, expr: &Expression<'i>) {
+		match expr.ty {
+			ExpressionType::Number(_) => (),
+			ExpressionType::Name(n) => {
+				if !scope.contains(n) && !self.globals.contains(n) && !self.custom_uniforms.contains(n)
+					&& !BUILTIN_CONSTS.with(|c| c.contains_key(n)) && !BUILTIN_UNIFORMS.with(|u| u.contains_key(n))
+					&& self.seen.insert(n) {
+					self.free.push((n, expr.span));
+				}
+			}
+			ExpressionType::Store(var) => {
+				self.walk(scope, &expr.children[0]);
+				scope.insert(var);
+			}
+			ExpressionType::Binary(_) | ExpressionType::Unary(_) | ExpressionType::FnCall(_) => {
+				for child in &expr.children {
+					self.walk(scope, child);
+				}
+			}
+			ExpressionType::Block | ExpressionType::While => {
+				let mut inner = scope.clone();
+				for child in &expr.children {
+					self.walk(&mut inner, child);
+				}
+			}
+			ExpressionType::If => {
+				self.walk(scope, &expr.children[0]);
+				self.walk(&mut scope.clone(), &expr.children[1]);
+				self.walk(&mut scope.clone(), &expr.children[2]);
+			}
+			ExpressionType::Sum { countvar: var }
+			| ExpressionType::Prod { countvar: var }
+			| ExpressionType::Iter { itervar: var } => {
+				self.walk(scope, &expr.children[0]);
+				self.walk(scope, &expr.children[1]);
+				let mut inner = scope.clone();
+				inner.insert(var);
+				self.walk(&mut inner, &expr.children[2]);
+			}
+		}
+	}
+}
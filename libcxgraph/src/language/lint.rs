@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+
+use super::{ENTRY_POINTS, ast::{Definition, Expression, ExpressionType, Span}, builtins::{BUILTIN_CONSTS, BUILTIN_FUNCS}, compiler::CompileWarning};
+
+// scoping here mirrors infer_expr in the compiler. a local is tracked
+// by name within its definition, so two blocks that each declare an x
+// share what is known about x. names starting with an underscore are
+// never reported as unused.
+
+/// Warnings for a program that compiles but likely does not do what was
+/// intended. `used` is the set of definitions reachable from the entry points
+pub fn lint<'i>(defns: &[Definition<'i>], used: &HashSet<&'i str>) -> Vec<CompileWarning> {
+	let mut warnings = Vec::new();
+	for defn in defns {
+		let name = defn.name();
+		let span = defn.name_span();
+		let (kind, shadows) = match defn {
+			Definition::Constant { .. } => ("constant", is_builtin_const(name)),
+			Definition::Function { .. } => ("function", BUILTIN_FUNCS.with(|f| f.contains_key(name))),
+		};
+		if shadows {
+			warnings.push(CompileWarning::new(format!("{kind} {name} shadows a builtin {kind}"), span));
+		}
+		if !used.contains(name) && !ENTRY_POINTS.contains(&name) {
+			warnings.push(CompileWarning::new(format!("{kind} {name} is never used"), span));
+		}
+
+		let (args, arg_spans) = match defn {
+			Definition::Constant { .. } => (&[][..], &[][..]),
+			Definition::Function { args, arg_spans, .. } => (&args[..], &arg_spans[..]),
+		};
+		let mut linter = Linter::default();
+		let scope: HashSet<&str> = args.iter().copied().collect();
+		linter.block(defn.value(), &scope, HashSet::new(), true);
+
+		for (arg, &span) in args.iter().zip(arg_spans) {
+			if is_builtin_const(arg) {
+				warnings.push(CompileWarning::new(format!("argument {arg} of {name} shadows a builtin constant"), span));
+			}
+			// the entry points are given their arguments whether they use them or not
+			let unused = !linter.reads.contains(arg) && !arg.starts_with('_');
+			if unused && !ENTRY_POINTS.contains(&name) {
+				warnings.push(CompileWarning::new(format!("argument {arg} of {name} is never used"), span));
+			}
+		}
+		warnings.append(&mut linter.warnings);
+		for store in &linter.stores {
+			if store.declares && is_builtin_const(store.name) {
+				warnings.push(CompileWarning::new(format!("local {} shadows a builtin constant", store.name), store.span));
+			}
+			if store.name.starts_with('_') {
+				continue
+			}
+			if !linter.reads.contains(store.name) {
+				if store.declares {
+					warnings.push(CompileWarning::new(format!("local {} is never used", store.name), store.span));
+				}
+			} else if !linter.live_stores.contains(&store.ptr) {
+				warnings.push(CompileWarning::new(format!("value stored to {} is never read", store.name), store.span));
+			}
+		}
+	}
+	warnings
+}
+
+fn is_builtin_const(name: &str) -> bool {
+	BUILTIN_CONSTS.with(|c| c.contains_key(name))
+}
+
+struct Store<'i> {
+	ptr: *const Expression<'i>,
+	name: &'i str,
+	span: Span,
+	// whether this is the first store to the local in its scope
+	declares: bool,
+}
+
+#[derive(Default)]
+struct Linter<'i> {
+	// locals read somewhere while in scope
+	reads: HashSet<&'i str>,
+	stores: Vec<Store<'i>>,
+	seen_stores: HashSet<*const Expression<'i>>,
+	// stores whose value may be read later
+	live_stores: HashSet<*const Expression<'i>>,
+	// warnings found while walking expressions, and the expressions
+	// they are for, as the bodies of loops are walked more than once
+	warnings: Vec<CompileWarning>,
+	warned: HashSet<*const Expression<'i>>,
+}
+
+// the live locals of an expression are those whose values on entry may be
+// read before they are next stored to. they are found walking backwards
+// from the locals live after the expression, `used` being whether the
+// expression's own value is used
+impl<'i> Linter<'i> {
+	fn warn(&mut self, expr: &Expression<'i>, msg: String) {
+		if self.warned.insert(expr) {
+			self.warnings.push(CompileWarning::new(msg, expr.span));
+		}
+	}
+
+	fn check_loop_var(&mut self, expr: &Expression<'i>, var: &str) {
+		if is_builtin_const(var) {
+			self.warn(expr, format!("loop variable {var} shadows a builtin constant"));
+		}
+	}
+
+	fn check_cond(&mut self, cond: &Expression<'i>) {
+		if let ExpressionType::Number(n) = cond.ty {
+			let msg = if n.re > 0.0 { "condition is always true" } else { "condition is always false" };
+			self.warn(cond, msg.to_owned());
+		}
+	}
+
+	// expressions evaluated in order in the same scope, all of whose values are used
+	fn seq(&mut self, exprs: &[Expression<'i>], scope: &HashSet<&'i str>, live: HashSet<&'i str>)
+	-> HashSet<&'i str> {
+		self.walk(exprs, scope, live, |_| true)
+	}
+
+	// expressions evaluated in order in a new scope, of which only the last value is used
+	fn block(&mut self, exprs: &[Expression<'i>], scope: &HashSet<&'i str>, live: HashSet<&'i str>, used: bool)
+	-> HashSet<&'i str> {
+		self.walk(exprs, scope, live, |i| used && i + 1 == exprs.len())
+	}
+
+	fn walk(&mut self, exprs: &[Expression<'i>], scope: &HashSet<&'i str>, mut live: HashSet<&'i str>, used: impl Fn(usize) -> bool)
+	-> HashSet<&'i str> {
+		let mut scopes = vec![scope.clone()];
+		for expr in exprs {
+			let mut next = scopes[scopes.len() - 1].clone();
+			declare(expr, &mut next);
+			scopes.push(next);
+		}
+		for (i, expr) in exprs.iter().enumerate().rev() {
+			live = self.live(expr, &scopes[i], live, used(i));
+		}
+		live
+	}
+
+	fn live(&mut self, expr: &Expression<'i>, scope: &HashSet<&'i str>, mut live: HashSet<&'i str>, used: bool)
+	-> HashSet<&'i str> {
+		match expr.ty {
+			ExpressionType::Number(_) => live,
+			ExpressionType::Name(n) => {
+				if scope.contains(n) {
+					self.reads.insert(n);
+					live.insert(n);
+				}
+				live
+			}
+			ExpressionType::Store(var) => {
+				if used {
+					self.reads.insert(var);
+					live.insert(var);
+				}
+				if self.seen_stores.insert(expr) {
+					self.stores.push(Store {
+						ptr: expr,
+						name: var,
+						span: expr.span,
+						declares: !scope.contains(var),
+					});
+				}
+				if live.remove(var) {
+					self.live_stores.insert(expr);
+				}
+				self.live(&expr.children[0], scope, live, true)
+			}
+			ExpressionType::Block => self.block(&expr.children, scope, live, used),
+			ExpressionType::Binary(_) | ExpressionType::Unary(_) | ExpressionType::FnCall(_)
+				=> self.seq(&expr.children, scope, live),
+			ExpressionType::If => {
+				self.check_cond(&expr.children[0]);
+				let mut branch_scope = scope.clone();
+				declare(&expr.children[0], &mut branch_scope);
+				let mut t = self.live(&expr.children[1], &branch_scope, live.clone(), used);
+				let f = self.live(&expr.children[2], &branch_scope, live, used);
+				t.extend(f);
+				self.live(&expr.children[0], scope, t, true)
+			}
+			ExpressionType::While => {
+				self.check_cond(&expr.children[0]);
+				let mut loop_scope = scope.clone();
+				declare(&expr.children[0], &mut loop_scope);
+				// live at the top of the loop, before the condition
+				let mut head = self.live(&expr.children[0], scope, live.clone(), true);
+				loop {
+					let mut after_cond = self.live(&expr.children[1], &loop_scope, head.clone(), used);
+					after_cond.extend(live.iter().copied());
+					let next = self.live(&expr.children[0], scope, after_cond, true);
+					if next == head {
+						return head
+					}
+					head = next;
+				}
+			}
+			ExpressionType::Sum { countvar }
+			| ExpressionType::Prod { countvar } => {
+				self.check_loop_var(expr, countvar);
+				let mut loop_scope = scope.clone();
+				declare(&expr.children[0], &mut loop_scope);
+				declare(&expr.children[1], &mut loop_scope);
+				loop_scope.insert(countvar);
+				// live at the top of the loop, before the count is stored
+				let mut head = live.clone();
+				loop {
+					let mut next = self.live(&expr.children[2], &loop_scope, head.clone(), true);
+					next.remove(countvar);
+					next.extend(live.iter().copied());
+					if next == head {
+						break
+					}
+					head = next;
+				}
+				self.seq(&expr.children[..2], scope, head)
+			}
+			ExpressionType::Iter { itervar } => {
+				self.check_loop_var(expr, itervar);
+				let mut loop_scope = scope.clone();
+				declare(&expr.children[0], &mut loop_scope);
+				declare(&expr.children[1], &mut loop_scope);
+				loop_scope.insert(itervar);
+				let mut exit = live;
+				if used {
+					exit.insert(itervar);
+				}
+				// live at the top of the loop, each iteration's body
+				// being stored to the iteration variable
+				let mut head = exit.clone();
+				loop {
+					let mut after_body = head.clone();
+					after_body.remove(itervar);
+					let mut next = self.live(&expr.children[2], &loop_scope, after_body, true);
+					next.extend(exit.iter().copied());
+					if next == head {
+						break
+					}
+					head = next;
+				}
+				head.remove(itervar);
+				self.seq(&expr.children[..2], scope, head)
+			}
+		}
+	}
+}
+
+// add the locals that evaluating expr declares in the enclosing scope
+fn declare<'i>(expr: &Expression<'i>, scope: &mut HashSet<&'i str>) {
+	let children = match expr.ty {
+		ExpressionType::Number(_) | ExpressionType::Name(_)
+		| ExpressionType::Block | ExpressionType::While => &[][..],
+		ExpressionType::Store(var) => {
+			declare(&expr.children[0], scope);
+			scope.insert(var);
+			return
+		}
+		ExpressionType::Binary(_) | ExpressionType::Unary(_) | ExpressionType::FnCall(_) => &expr.children[..],
+		ExpressionType::If => &expr.children[..1],
+		ExpressionType::Sum { .. } | ExpressionType::Prod { .. } | ExpressionType::Iter { .. } => &expr.children[..2],
+	};
+	for child in children {
+		declare(child, scope);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::language::{optimize::live_definitions, syntax::ProgramParser, token::Lexer};
+
+	// each warning with the source text it points at
+	fn warnings(src: &str) -> Vec<(String, &str)> {
+		let defns = ProgramParser::new().parse(src, Lexer::new(src)).unwrap().defns;
+		let used = live_definitions(&defns, &HashSet::new());
+		lint(&defns, &used).into_iter()
+			.map(|w| (w.message().to_owned(), &src[w.span().start..w.span().end]))
+			.collect()
+	}
+
+	#[test]
+	fn unused_definitions_and_arguments() {
+		let src = "f(x, y) = x + 1\nc = 2\nplot(z) = 1\ncolor(z) = rgb(1, 1, 1)";
+		assert_eq!(warnings(src), [
+			("function f is never used".to_owned(), "f"),
+			("argument y of f is never used".to_owned(), "y"),
+			("constant c is never used".to_owned(), "c"),
+		]);
+	}
+}
@@ -1,30 +1,152 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use lalrpop_util::lalrpop_mod;
 
-use crate::language::token::Lexer;
+use crate::{language::token::Lexer, renderer::{fragment_src, CustomUniform}};
 
-use self::{ast::display_def, compiler::Compiler, token::{LexerError, Token}};
+use self::{ast::{display_def, display_param, Definition}, compiler::Compiler, free_vars::find_free_variables, lint::lint, optimize::{fold_constants, hoist_constants, live_definitions}, params::resolve_params, token::{LexerError, Token}, validate::validate};
 
 mod token;
 mod ast;
 mod compiler;
 mod builtins;
+mod eval;
+mod free_vars;
+mod lint;
+mod optimize;
+mod params;
+mod suggest;
+mod types;
+mod validate;
+
+pub use ast::Span;
+pub use compiler::{CompileError, CompileWarning};
+pub(crate) use compiler::format_custom_uniform;
+pub use eval::HostConstants;
+pub use params::{ParamInfo, ParamKind};
 
 lalrpop_mod!(pub syntax, "/language/syntax.rs");
 
-pub fn compile(src: &str, vars: &HashMap<String, usize>) -> Result<String, Box<dyn std::error::Error>> {
+/// Number of user variables, including parameters, that a program
+/// may use. They are packed two to a vec4f in their own uniform buffer
+pub const MAX_VARIABLES: usize = 256;
+
+// the functions a program can define that the fragment shader calls
+const ENTRY_POINTS: &[&str] = &["plot", "color", "decor"];
+
+/// Number of global constants that can be evaluated on the host and
+/// passed to the shader as uniforms, see `hoist_constants`. The uniform
+/// buffer is sized for the constants each program actually hoists, and
+/// this many take 8 KiB of the 16 KiB every backend allows
+pub const MAX_HOST_CONSTANTS: usize = 1024;
+
+pub struct CompiledProgram {
+	pub wgsl: String,
+	pub constants: HostConstants,
+	pub warnings: Vec<CompileWarning>,
+	/// The parameters declared in the program. Their slots, and those of the
+	/// variables given to `compile`, are the indices of the user variables
+	pub params: Vec<ParamInfo>,
+}
+
+/// Compile a program to WGSL. `vars` gives the slot of each user variable, and
+/// `custom_uniforms` should be those the shader will be loaded with
+pub fn compile(src: &str, vars: &HashMap<String, usize>, custom_uniforms: &[CustomUniform])
+-> Result<CompiledProgram, Box<dyn std::error::Error>> {
 	let lexer = Lexer::new(src);
-	let result = syntax::ProgramParser::new()
+	let program = syntax::ProgramParser::new()
 		.parse(src, lexer)
 		.map_err(|e| e.to_string())?;
+	let custom_names = custom_uniform_names(custom_uniforms)?;
+	let mut vars = vars.clone();
+	let params = resolve_params(&program.params, &program.defns, &mut vars)?;
+	let vars = &vars;
+	let bounded_vars = program.params.iter()
+		.filter(|param| param.range.is_some())
+		.map(|param| param.name)
+		.collect();
+
+	ensure_entry_points_are_functions(&program.defns)?;
+
+	let mut result = program.defns;
+	// folding inlines constants, so find which are used first
+	let used = live_definitions(&result, &HashSet::new());
+	fold_constants(&mut result, vars, &custom_names);
+	let mut warnings = lint(&result, &used);
+
+	let live = live_definitions(&result, &HashSet::new());
+	let (hoisted, hoist_warnings) = hoist_constants(&result, &live, vars, &custom_names, MAX_HOST_CONSTANTS);
+	warnings.extend(hoist_warnings);
+	let host_consts: HashMap<&str, usize> = hoisted.iter()
+		.enumerate()
+		.map(|(i, (name, _))| (*name, i))
+		.collect();
+	let host_names = host_consts.keys().copied().collect();
+	let live = live_definitions(&result, &host_names);
+
 	let mut wgsl = String::new();
-	let mut cmp = Compiler::new(&mut wgsl, vars);
-	for defn in result {
-		cmp.compile_defn(&defn)?;
+	let mut cmp = Compiler::new(&mut wgsl, vars, &bounded_vars, &host_consts, custom_uniforms);
+	cmp.declare_variables()?;
+	for defn in &result {
+		if live.contains(defn.name()) && !host_consts.contains_key(defn.name()) {
+			cmp.compile_defn(defn)?;
+		} else {
+			cmp.check_defn(defn)?;
+		}
 	}
 	cmp.ensure_plot_defined()?;
-	Ok(wgsl)
+	cmp.define_plot_color()?;
+	cmp.define_plot_decor()?;
+	let line_spans = cmp.line_spans().to_vec();
+	warnings.extend(cmp.take_warnings());
+	warnings.sort_by_key(|w| w.span().start);
+	validate(&fragment_src(custom_uniforms, hoisted.len()), &wgsl, &line_spans)?;
+	Ok(CompiledProgram {
+		wgsl,
+		constants: HostConstants(hoisted.into_iter().map(|(_, expr)| expr).collect()),
+		warnings,
+		params,
+	})
+}
+
+/// Names a program uses that are not builtins, custom uniforms, parameters, globals or locals, which
+/// would have to be user variables for it to compile, with the span of each one's first use
+pub fn free_variables<'i>(src: &'i str, custom_uniforms: &[CustomUniform])
+-> Result<Vec<(&'i str, Span)>, Box<dyn std::error::Error>> {
+	let lexer = Lexer::new(src);
+	let result = syntax::ProgramParser::new()
+		.parse(src, lexer)
+		.map_err(|e| e.to_string())?;
+	let custom_names = custom_uniform_names(custom_uniforms)?;
+	Ok(find_free_variables(&result.params, &result.defns, &custom_names))
+}
+
+// the fragment shader calls plot, color and decor with the point being
+// drawn, so defining one as a constant would otherwise be ignored
+fn ensure_entry_points_are_functions(defns: &[Definition]) -> Result<(), CompileError> {
+	for defn in defns {
+		if let Definition::Constant { name, .. } = defn {
+			if ENTRY_POINTS.contains(name) {
+				let msg = format!("{name} must be a function of one argument, not a constant");
+				return Err(CompileError::new(msg, Some(defn.span())))
+			}
+		}
+	}
+	Ok(())
+}
+
+// custom uniforms are read as names, so each must lex as one
+fn custom_uniform_names(custom_uniforms: &[CustomUniform]) -> Result<HashSet<&str>, CompileError> {
+	let mut names = HashSet::new();
+	for u in custom_uniforms {
+		if !matches!(tokens(&u.name).as_deref(), Ok([(_, Token::Name(_), _)])) {
+			return Err(format!("custom uniform {:?} is not a valid name", u.name).into())
+		}
+		if !names.insert(u.name.as_str()) {
+			return Err(format!("custom uniform {} is added more than once", u.name).into())
+		}
+	}
+	Ok(names)
 }
 
 pub fn tokens(src: &str) -> Result<Vec<(usize, Token, usize)>, LexerError> {
@@ -37,7 +159,10 @@ pub fn show_ast(src: &str) -> Result<String, Box<dyn std::error::Error>> {
 		.parse(src, lexer)
 		.map_err(|e| e.to_string())?;
 	let mut buf = String::new();
-	for defn in result {
+	for param in &result.params {
+		display_param(&mut buf, param)?;
+	}
+	for defn in result.defns {
 		display_def(&mut buf, &defn)?;
 	}
 	Ok(buf)
@@ -0,0 +1,479 @@
+use std::collections::{HashMap, HashSet};
+
+use num_complex::Complex64 as Complex;
+
+use super::{ENTRY_POINTS, compiler::CompileWarning, ast::{Definition, Expression, ExpressionType}, builtins::{Arity, BUILTIN_CONSTS, BUILTIN_FUNCS, BUILTIN_UNIFORMS}, eval::{eval_binary, eval_builtin, eval_unary, has_host_impl, HostExpr}};
+
+// name resolution here mirrors the compiler: locals shadow global
+// constants, which shadow variables, which shadow custom uniforms,
+// which shadow builtin uniforms, which shadow builtin constants,
+// and a global function only shadows a builtin after its definition.
+// anything that does not resolve the way we expect is left alone so
+// that the compiler can report it.
+
+pub fn fold_constants<'i>(defns: &mut [Definition<'i>], vars: &HashMap<String, usize>, custom_uniforms: &HashSet<&str>) {
+	let mut folder = Folder {
+		vars,
+		custom_uniforms,
+		global_funcs: HashSet::new(),
+		global_consts: HashMap::new(),
+	};
+	for defn in defns {
+		folder.fold_defn(defn);
+	}
+}
+
+/// The value of an expression made only of numbers and builtins
+pub fn const_value(expr: &Expression) -> Option<Complex> {
+	let mut folder = Folder {
+		vars: &HashMap::new(),
+		custom_uniforms: &HashSet::new(),
+		global_funcs: HashSet::new(),
+		global_consts: HashMap::new(),
+	};
+	folder.fold_expr(&mut HashSet::new(), &mut expr.clone())
+}
+
+/// Names of the definitions reachable from the entry points, without
+/// looking inside the definitions in `opaque`
+pub fn live_definitions<'i>(defns: &[Definition<'i>], opaque: &HashSet<&'i str>) -> HashSet<&'i str> {
+	let refs: HashMap<&str, HashSet<&str>> = defns.iter()
+		.map(|defn| {
+			let mut refs = HashSet::new();
+			if !opaque.contains(defn.name()) {
+				for expr in defn.value() {
+					collect_refs(expr, &mut refs);
+				}
+			}
+			(defn.name(), refs)
+		})
+		.collect();
+
+	let mut live = HashSet::new();
+	let mut stack = ENTRY_POINTS.to_vec();
+	while let Some(name) = stack.pop() {
+		if let Some(r) = refs.get(name) {
+			if live.insert(name) {
+				stack.extend(r.iter().copied());
+			}
+		}
+	}
+	live
+}
+
+/// Live constants that only depend on user variables and can be evaluated
+/// on the host, at most `max` of them. The index of each in the result
+/// is its uniform slot. A constant that depends on a user variable but
+/// is left to be computed for every pixel is warned about
+pub fn hoist_constants<'i>(defns: &[Definition<'i>], live: &HashSet<&'i str>, vars: &HashMap<String, usize>,
+custom_uniforms: &HashSet<&str>, max: usize) -> (Vec<(&'i str, HostExpr)>, Vec<CompileWarning>) {
+	let mut hoister = Hoister {
+		vars,
+		custom_uniforms,
+		global_funcs: HashSet::new(),
+		global_consts: HashMap::new(),
+		var_deps: HashMap::new(),
+		locals: HashMap::new(),
+		local_count: 0,
+	};
+	let mut hoisted = Vec::new();
+	let mut warnings = Vec::new();
+	for defn in defns {
+		match defn {
+			Definition::Function { name, .. } => { hoister.global_funcs.insert(name); },
+			Definition::Constant { name, value, .. } => {
+				if let Some(var) = value.iter().find_map(|expr| hoister.var_dependency(expr)) {
+					hoister.var_deps.insert(name, var);
+				}
+				let mut slot = None;
+				if live.contains(name) {
+					let reason = match hoister.host_value(value) {
+						Ok(expr) if hoisted.len() < max => {
+							slot = Some(hoisted.len());
+							hoisted.push((*name, expr));
+							None
+						}
+						Ok(_) => Some(format!("at most {max} constants can be computed once per frame")),
+						Err(reason) => reason,
+					};
+					if let (Some(reason), Some(var)) = (reason, hoister.var_deps.get(name)) {
+						let msg = format!("constant {name} depends on variable {var}, but is computed for every pixel since {reason}");
+						warnings.push(CompileWarning::new(msg, defn.span()));
+					}
+				}
+				hoister.global_consts.insert(name, slot);
+			}
+		}
+	}
+	(hoisted, warnings)
+}
+
+struct Hoister<'v, 'i> {
+	vars: &'v HashMap<String, usize>,
+	custom_uniforms: &'v HashSet<&'v str>,
+	global_funcs: HashSet<&'i str>,
+	// the slot of each global constant, if it was hoisted
+	global_consts: HashMap<&'i str, Option<usize>>,
+	// a user variable that each global constant depends on, if any
+	var_deps: HashMap<&'i str, &'v str>,
+	// the index of each local in scope in the constant being hoisted
+	locals: HashMap<&'i str, usize>,
+	local_count: usize,
+}
+
+// why a constant cannot be hoisted, if it is worth warning about.
+// names that do not resolve are left for the compiler to report, and
+// constants that use one that was not hoisted are not warned about again
+type NotHoisted = Option<String>;
+
+impl<'v, 'i> Hoister<'v, 'i> {
+	fn host_value(&mut self, value: &[Expression<'i>]) -> Result<HostExpr, NotHoisted> {
+		self.locals.clear();
+		self.local_count = 0;
+		let exprs = value.iter()
+			.map(|expr| self.host_expr(expr))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(match <[HostExpr; 1]>::try_from(exprs) {
+			Ok([expr]) => expr,
+			Err(exprs) => HostExpr::Block(exprs),
+		})
+	}
+
+	fn host_expr(&mut self, expr: &Expression<'i>) -> Result<HostExpr, NotHoisted> {
+		match expr.ty {
+			ExpressionType::Number(n) => Ok(HostExpr::Number(n)),
+			ExpressionType::Name(n) => {
+				if let Some(local) = self.locals.get(n) {
+					Ok(HostExpr::Local(*local))
+				} else if let Some(slot) = self.global_consts.get(n) {
+					slot.map(HostExpr::Constant).ok_or(None)
+				} else if let Some(var) = self.vars.get(n) {
+					Ok(HostExpr::Variable(*var))
+				} else if self.custom_uniforms.contains(n) || BUILTIN_UNIFORMS.with(|u| u.contains_key(n)) {
+					Err(Some(format!("it reads {n}, which is only known on the GPU")))
+				} else {
+					BUILTIN_CONSTS.with(|c| c.get(n).map(|c| HostExpr::Number(c.1)).ok_or(None))
+				}
+			}
+			ExpressionType::Store(var) => {
+				let a = self.host_expr(&expr.children[0])?;
+				let local = *self.locals.entry(var).or_insert_with(|| {
+					self.local_count += 1;
+					self.local_count - 1
+				});
+				Ok(HostExpr::Store(local, Box::new(a)))
+			}
+			ExpressionType::Block => {
+				// locals first stored in the block go out of scope after it
+				let outer = self.locals.clone();
+				let exprs = expr.children.iter()
+					.map(|child| self.host_expr(child))
+					.collect::<Result<Vec<_>, _>>();
+				self.locals = outer;
+				Ok(HostExpr::Block(exprs?))
+			}
+			ExpressionType::Binary(op) => {
+				let a = self.host_expr(&expr.children[0])?;
+				let b = self.host_expr(&expr.children[1])?;
+				Ok(HostExpr::Binary(op, Box::new(a), Box::new(b)))
+			}
+			ExpressionType::Unary(op) => {
+				let a = self.host_expr(&expr.children[0])?;
+				Ok(HostExpr::Unary(op, Box::new(a)))
+			}
+			ExpressionType::FnCall(f) => {
+				if self.global_funcs.contains(f) {
+					return Err(Some(format!("it calls the function {f}")))
+				}
+				let (func, arity) = BUILTIN_FUNCS.with(|c| c.get(f).copied()).ok_or(None)?;
+				let args = expr.children.iter()
+					.map(|child| self.host_expr(child))
+					.collect::<Result<Vec<_>, _>>()?;
+				let argc = match arity {
+					Arity::Fixed(argc) if argc == args.len() => argc,
+					Arity::Variadic(argc) if argc <= args.len() => 2,
+					_ => return Err(None),
+				};
+				if !has_host_impl(func, argc) {
+					return Err(Some(format!("{f} can only be computed on the GPU")))
+				}
+				match arity {
+					Arity::Fixed(_) => Ok(HostExpr::Call(func, args)),
+					Arity::Variadic(_) => {
+						let mut args = args.into_iter();
+						let first = args.next().ok_or(None)?;
+						Ok(args.fold(first, |acc, arg| HostExpr::Call(func, vec![acc, arg])))
+					}
+				}
+			}
+			ExpressionType::If | ExpressionType::While | ExpressionType::Sum { .. }
+			| ExpressionType::Prod { .. } | ExpressionType::Iter { .. }
+				=> Err(Some("it uses an if or a loop".to_owned())),
+		}
+	}
+
+	// a user variable that expr depends on, directly or through another constant
+	fn var_dependency(&self, expr: &Expression) -> Option<&'v str> {
+		if let ExpressionType::Name(n) = expr.ty {
+			if let Some(var) = self.var_deps.get(n) {
+				return Some(var)
+			}
+			if !self.global_consts.contains_key(n) {
+				if let Some((var, _)) = self.vars.get_key_value(n) {
+					return Some(var)
+				}
+			}
+		}
+		expr.children.iter().find_map(|child| self.var_dependency(child))
+	}
+}
+
+fn collect_refs<'i>(expr: &Expression<'i>, refs: &mut HashSet<&'i str>) {
+	match expr.ty {
+		ExpressionType::Name(n) | ExpressionType::FnCall(n) => { refs.insert(n); },
+		_ => (),
+	}
+	for child in &expr.children {
+		collect_refs(child, refs);
+	}
+}
+
+struct Folder<'v, 'i> {
+	vars: &'v HashMap<String, usize>,
+	custom_uniforms: &'v HashSet<&'v str>,
+	global_funcs: HashSet<&'i str>,
+	// the value of each global constant, if it is known
+	global_consts: HashMap<&'i str, Option<Complex>>,
+}
+
+impl<'v, 'i> Folder<'v, 'i> {
+	fn fold_defn(&mut self, defn: &mut Definition<'i>) {
+		match defn {
+			Definition::Function { name, args, value, .. } => {
+				let mut local: HashSet<&str> = args.iter().copied().collect();
+				for expr in value.iter_mut() {
+					self.fold_expr(&mut local, expr);
+				}
+				self.global_funcs.insert(name);
+			}
+			Definition::Constant { name, value, .. } => {
+				let mut local = HashSet::new();
+				for expr in value.iter_mut() {
+					self.fold_expr(&mut local, expr);
+				}
+				let result = if value.iter().all(is_number) {
+					value.last().and_then(as_number)
+				} else {
+					None
+				};
+				self.global_consts.insert(name, result);
+			}
+		}
+	}
+
+	// fold expr in place, giving its value if it is known, even
+	// when it could not be written back as an f32 literal
+	fn fold_expr(&mut self, local: &mut HashSet<&'i str>, expr: &mut Expression<'i>) -> Option<Complex> {
+		let folded = match expr.ty {
+			ExpressionType::Number(n) => return Some(n),
+			ExpressionType::Name(n) => self.resolve_const(local, n),
+			ExpressionType::Store(var) => {
+				self.fold_expr(local, &mut expr.children[0]);
+				local.insert(var);
+				None
+			}
+			ExpressionType::Block => {
+				let mut block_local = local.clone();
+				for child in &mut expr.children {
+					self.fold_expr(&mut block_local, child);
+				}
+				if expr.children.iter().all(is_number) {
+					expr.children.last().and_then(as_number)
+				} else {
+					None
+				}
+			}
+			ExpressionType::Binary(op) => {
+				self.fold_children(local, expr);
+				match (as_number(&expr.children[0]), as_number(&expr.children[1])) {
+					(Some(a), Some(b)) => Some(eval_binary(op, a, b)),
+					_ => None,
+				}
+			}
+			ExpressionType::Unary(op) => {
+				self.fold_children(local, expr);
+				as_number(&expr.children[0]).map(|a| eval_unary(op, a))
+			}
+			ExpressionType::FnCall(f) => {
+				self.fold_children(local, expr);
+				let args: Option<Vec<Complex>> = expr.children.iter().map(as_number).collect();
+				args.and_then(|args| self.eval_call(f, &args))
+			}
+			ExpressionType::If => {
+				self.fold_children(local, expr);
+				None
+			}
+			ExpressionType::While => {
+				self.fold_expr(local, &mut expr.children[0]);
+				self.fold_expr(&mut local.clone(), &mut expr.children[1]);
+				None
+			}
+			ExpressionType::Sum { countvar: var }
+			| ExpressionType::Prod { countvar: var }
+			| ExpressionType::Iter { itervar: var } => {
+				self.fold_expr(local, &mut expr.children[0]);
+				self.fold_expr(local, &mut expr.children[1]);
+				let mut loop_local = local.clone();
+				loop_local.insert(var);
+				self.fold_expr(&mut loop_local, &mut expr.children[2]);
+				None
+			}
+		};
+
+		// values that cannot be written as f32 literals are left for the gpu
+		if let Some(n) = folded {
+			if n.re.abs() <= f32::MAX as f64 && n.im.abs() <= f32::MAX as f64 {
+				*expr = Expression { ty: ExpressionType::Number(n), children: Vec::with_capacity(0), span: expr.span };
+			}
+		}
+		folded
+	}
+
+	fn fold_children(&mut self, local: &mut HashSet<&'i str>, expr: &mut Expression<'i>) {
+		for child in &mut expr.children {
+			self.fold_expr(local, child);
+		}
+	}
+
+	fn resolve_const(&self, local: &HashSet<&'i str>, name: &str) -> Option<Complex> {
+		if local.contains(name) {
+			None
+		} else if let Some(value) = self.global_consts.get(name) {
+			*value
+		} else if self.vars.contains_key(name) || self.custom_uniforms.contains(name)
+			|| BUILTIN_UNIFORMS.with(|u| u.contains_key(name)) {
+			None
+		} else {
+			BUILTIN_CONSTS.with(|c| Some(c.get(name)?.1))
+		}
+	}
+
+	fn eval_call(&self, name: &str, args: &[Complex]) -> Option<Complex> {
+		if self.global_funcs.contains(name) {
+			return None
+		}
+		let (func, arity) = BUILTIN_FUNCS.with(|c| c.get(name).copied())?;
+		match arity {
+			Arity::Fixed(argc) if argc == args.len() => eval_builtin(func, args),
+			Arity::Variadic(argc) if argc <= args.len() => {
+				let (first, rest) = args.split_first()?;
+				rest.iter().try_fold(*first, |acc, arg| eval_builtin(func, &[acc, *arg]))
+			}
+			_ => None,
+		}
+	}
+}
+
+fn as_number(expr: &Expression) -> Option<Complex> {
+	match expr.ty {
+		ExpressionType::Number(n) => Some(n),
+		_ => None,
+	}
+}
+
+fn is_number(expr: &Expression) -> bool {
+	matches!(expr.ty, ExpressionType::Number(_))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::language::{syntax::ProgramParser, token::Lexer};
+
+	// the definitions of src after folding, with a as a user variable
+	fn fold(src: &str) -> Vec<Definition<'_>> {
+		let mut defns = ProgramParser::new().parse(src, Lexer::new(src)).unwrap().defns;
+		let vars = HashMap::from([("a".to_owned(), 0)]);
+		fold_constants(&mut defns, &vars, &HashSet::new());
+		defns
+	}
+
+	// the value c folds to, if it folds to a number
+	fn folded(src: &str) -> Option<Complex> {
+		let defns = fold(src);
+		let defn = defns.iter().find(|defn| defn.name() == "c").unwrap();
+		match defn.value() {
+			[expr] => as_number(expr),
+			_ => None,
+		}
+	}
+
+	fn real(x: f64) -> Option<Complex> {
+		Some(Complex::new(x, 0.0))
+	}
+
+	#[test]
+	fn integer_powers_are_exact() {
+		assert_eq!(folded("c = 2^4"), real(16.0));
+		assert_eq!(folded("c = 3^3"), real(27.0));
+		assert_eq!(folded("c = (-2)^3"), real(-8.0));
+		assert_eq!(folded("c = 10^10"), real(1e10));
+		assert_eq!(folded("c = 2^-2"), real(0.25));
+		assert_eq!(folded("c = 7^0"), real(1.0));
+		assert_eq!(folded("c = i^2"), real(-1.0));
+		assert_eq!(folded("c = (1 + i)^4"), real(-4.0));
+	}
+
+	#[test]
+	fn other_powers() {
+		let c = folded("c = 2^0.5").unwrap();
+		assert!((c.re - 2f64.sqrt()).abs() < 1e-15 && c.im == 0.0);
+		let c = folded("c = (-1)^0.5").unwrap();
+		assert!(c.re.abs() < 1e-15 && (c.im - 1.0).abs() < 1e-15);
+	}
+
+	#[test]
+	fn comparisons_after_folding() {
+		assert_eq!(folded("c = 2^3 == 8"), real(1.0));
+		assert_eq!(folded("c = 2^3 != 8"), real(0.0));
+		assert_eq!(folded("c = 3^2 >= 9"), real(1.0));
+		assert_eq!(folded("c = 10^2 < 100"), real(0.0));
+		let defns = fold("c = if(2^3 == 8) {1} {0}");
+		assert_eq!(as_number(&defns[0].value()[0].children[0]), real(1.0));
+	}
+
+	#[test]
+	fn floor_of_folded_values() {
+		assert_eq!(folded("c = floor(2^4)"), real(16.0));
+		assert_eq!(folded("c = floor(10^3)"), real(1000.0));
+		assert_eq!(folded("c = ceil(2^-1)"), real(1.0));
+		assert_eq!(folded("c = floor(3^2 / 3)"), real(3.0));
+	}
+
+	#[test]
+	fn constants_and_builtins_fold() {
+		assert_eq!(folded("b = 2^3\nc = b + 1"), real(9.0));
+		assert_eq!(folded("c = max(1, 4, 2)"), real(4.0));
+		assert_eq!(folded("c = 1 -> x, x + 1"), None);
+	}
+
+	#[test]
+	fn live_definitions_follow_entry_points() {
+		let src = "f(x) = x\ng(x) = f(x)\nh(x) = x\nk = 2\nplot(z) = g(z) + k\ndecor(w) = h(w)";
+		let defns = fold(src);
+		let live = live_definitions(&defns, &HashSet::new());
+		assert_eq!(live, HashSet::from(["f", "g", "h", "plot", "decor"]));
+		let live = live_definitions(&defns, &HashSet::from(["g"]));
+		assert_eq!(live, HashSet::from(["g", "h", "plot", "decor"]));
+	}
+
+	#[test]
+	fn unknown_values_are_left() {
+		assert_eq!(folded("c = a^2"), None);
+		assert_eq!(folded("c = t + 1"), None);
+		assert_eq!(folded("f(x) = x\nc = f(1)"), None);
+		// no f32 literal can hold these
+		assert_eq!(folded("c = 10^40"), None);
+		assert_eq!(folded("c = 1/0"), None);
+	}
+}
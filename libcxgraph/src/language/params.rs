@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+
+use num_complex::Complex64 as Complex;
+
+use super::{ast::{Definition, Expression, Param}, compiler::CompileError, optimize::const_value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+	/// A real number, shown as a slider
+	Real,
+	/// A complex number, shown as a point on the plot
+	Point,
+}
+
+/// A user variable declared in the program with `param`
+#[derive(Clone, Debug)]
+pub struct ParamInfo {
+	pub name: String,
+	pub kind: ParamKind,
+	/// Index of the user variable holding the parameter's value
+	pub slot: usize,
+	pub range: Option<(f64, f64)>,
+	pub step: Option<f64>,
+	pub default: Complex,
+}
+
+/// Check the parameters of a program and give each a user variable slot,
+/// reusing the slot of a variable with the same name if there is one
+pub fn resolve_params(params: &[Param], defns: &[Definition], vars: &mut HashMap<String, usize>)
+-> Result<Vec<ParamInfo>, CompileError> {
+	let globals: HashSet<&str> = defns.iter().map(Definition::name).collect();
+	let mut next_slot = vars.values().map(|slot| slot + 1).max().unwrap_or(0);
+	let mut result: Vec<ParamInfo> = Vec::with_capacity(params.len());
+	for param in params {
+		let error = |msg: String| CompileError::new(msg, Some(param.span));
+		let name = param.name;
+		if result.iter().any(|p| p.name == name) {
+			return Err(error(format!("parameter {name} is declared more than once")))
+		}
+		if globals.contains(name) {
+			return Err(error(format!("parameter {name} has the same name as a global")))
+		}
+		let kind = match param.kind {
+			"real" => ParamKind::Real,
+			"point" => ParamKind::Point,
+			kind => return Err(error(format!("unknown parameter kind {kind}, expected real or point"))),
+		};
+
+		let (range, step) = match (&param.range, kind) {
+			(None, _) => (None, None),
+			(Some(_), ParamKind::Point) => return Err(error(format!("point parameter {name} cannot have a range"))),
+			(Some(bounds), ParamKind::Real) => {
+				let bounds: Vec<f64> = bounds.iter()
+					.map(|expr| real_value(expr, name))
+					.collect::<Result<_, _>>()?;
+				let (min, max, step) = match bounds[..] {
+					[min, max] => (min, max, None),
+					[min, max, step] => (min, max, Some(step)),
+					_ => return Err(error(format!("range of parameter {name} should be [min, max] or [min, max, step]"))),
+				};
+				if min >= max {
+					return Err(error(format!("range of parameter {name} is empty")))
+				}
+				if step.is_some_and(|step| step <= 0.0) {
+					return Err(error(format!("step of parameter {name} must be positive")))
+				}
+				(Some((min, max)), step)
+			}
+		};
+
+		let default = match (&param.default, kind) {
+			(None, _) => match range {
+				Some((min, max)) => Complex::new(0.0f64.clamp(min, max), 0.0),
+				None => Complex::new(0.0, 0.0),
+			},
+			(Some(expr), ParamKind::Real) => {
+				let value = real_value(expr, name)?;
+				if range.is_some_and(|(min, max)| value < min || value > max) {
+					return Err(CompileError::new(format!("default of parameter {name} is outside its range"), Some(expr.span)))
+				}
+				Complex::new(value, 0.0)
+			}
+			(Some(expr), ParamKind::Point) => param_value(expr, name)?,
+		};
+
+		let slot = *vars.entry(name.to_owned()).or_insert_with(|| {
+			next_slot += 1;
+			next_slot - 1
+		});
+		result.push(ParamInfo { name: name.to_owned(), kind, slot, range, step, default });
+	}
+	Ok(result)
+}
+
+// a value given for a parameter, which must fit in the f32 it is stored in
+fn param_value(expr: &Expression, name: &str) -> Result<Complex, CompileError> {
+	let error = |msg: String| CompileError::new(msg, Some(expr.span));
+	let value = const_value(expr)
+		.ok_or_else(|| error(format!("values given for parameter {name} must be constant")))?;
+	if !(value.re.abs() <= f32::MAX as f64 && value.im.abs() <= f32::MAX as f64) {
+		return Err(error(format!("values given for parameter {name} must be finite and at most {:e} in size", f32::MAX)))
+	}
+	Ok(value)
+}
+
+fn real_value(expr: &Expression, name: &str) -> Result<f64, CompileError> {
+	let value = param_value(expr, name)?;
+	if value.im != 0.0 {
+		let msg = format!("values given for real parameter {name} must be real");
+		return Err(CompileError::new(msg, Some(expr.span)))
+	}
+	Ok(value.re)
+}
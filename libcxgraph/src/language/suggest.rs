@@ -0,0 +1,79 @@
+use super::builtins::Arity;
+
+// greek letters used in builtin names, compared as their spelled out
+// names too, so that eg. psi suggests ψ
+const GREEK_NAMES: &[(char, &str)] = &[
+	('\u{0393}', "Gamma"),
+	('\u{03B3}', "gamma"),
+	('\u{03C4}', "tau"),
+	('\u{03C6}', "phi"),
+	('\u{03C8}', "psi"),
+];
+
+fn spelled_out(name: &str) -> Option<String> {
+	if !name.chars().any(|c| GREEK_NAMES.iter().any(|(g, _)| *g == c)) {
+		return None
+	}
+	let mut result = String::new();
+	for c in name.chars() {
+		match GREEK_NAMES.iter().find(|(g, _)| *g == c) {
+			Some((_, spelled)) => result.push_str(spelled),
+			None => result.push(c),
+		}
+	}
+	Some(result)
+}
+
+// number of single character insertions, deletions, substitutions and
+// transpositions of adjacent characters to turn a into b, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+	let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+	// rows i-2, i-1 and i of the distances between prefixes of a and b
+	let mut prev2 = vec![0; b.len() + 1];
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	let mut row = vec![0; b.len() + 1];
+	for i in 1..=a.len() {
+		row[0] = i;
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				row[j] = row[j].min(prev2[j - 2] + 1);
+			}
+		}
+		std::mem::swap(&mut prev2, &mut prev);
+		std::mem::swap(&mut prev, &mut row);
+	}
+	prev[b.len()]
+}
+
+/// The candidate closest to name, if any is close enough to be
+/// a likely misspelling, as a suffix for an error message
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+	let max_distance = (name.chars().count() / 3).max(1);
+	let best = candidates.into_iter()
+		.filter(|c| *c != name)
+		.filter_map(|c| {
+			let d = match spelled_out(c) {
+				Some(s) => edit_distance(name, c).min(edit_distance(name, &s)),
+				None => edit_distance(name, c),
+			};
+			(d <= max_distance).then_some((d, c))
+		})
+		.min();
+	match best {
+		Some((_, c)) => format!(", did you mean {c}?"),
+		None => String::new(),
+	}
+}
+
+/// The signature of a builtin, for error messages
+pub fn builtin_signature(name: &str, arity: Arity) -> String {
+	let arg = |i: usize| format!("z{}", char::from_u32(0x2081 + i as u32).unwrap_or('_'));
+	match arity {
+		Arity::Fixed(1) => format!("{name}(z)"),
+		Arity::Fixed(n) => format!("{name}({})", (0..n).map(arg).collect::<Vec<_>>().join(", ")),
+		Arity::Variadic(n) => format!("{name}({}, ...)", (0..n).map(arg).collect::<Vec<_>>().join(", ")),
+	}
+}
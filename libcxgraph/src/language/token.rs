@@ -8,8 +8,10 @@ pub enum Token<'i> {
 	Number(f64),
 	Name(&'i str),
 	Sum, Prod, Iter, If, While,
+	Param, In,
 	LParen, RParen,
 	LBrace, RBrace,
+	LBracket, RBracket,
 	Plus, Minus, Star, Slash, Caret,
 	Greater, Less, GreaterEqual, LessEqual,
 	EqualEqual, BangEqual,
@@ -27,10 +29,14 @@ impl<'i> fmt::Display for Token<'i> {
 			Token::Iter    => f.write_str("iter"),
 			Token::If      => f.write_str("if"),
 			Token::While   => f.write_str("while"),
+			Token::Param   => f.write_str("param"),
+			Token::In      => f.write_str("in"),
 			Token::LParen  => f.write_str("("),
 			Token::RParen  => f.write_str(")"),
 			Token::LBrace  => f.write_str("{"),
 			Token::RBrace  => f.write_str("}"),
+			Token::LBracket => f.write_str("["),
+			Token::RBracket => f.write_str("]"),
 			Token::Plus    => f.write_str("+"),
 			Token::Minus   => f.write_str("-"),
 			Token::Star    => f.write_str("*"),
@@ -74,6 +80,10 @@ pub struct Lexer<'i> {
 	src: &'i str,
 	chars: Peekable<CharIndices<'i>>,
 	bracket_depth: isize,
+	// param and in are only keywords at the start of a parameter
+	// declaration and before its range, so they can still be used as names
+	line_start: bool,
+	param_tokens: Option<usize>,
 }
 
 fn is_ident_begin(c: char) -> bool {
@@ -90,7 +100,9 @@ impl<'i> Lexer<'i> {
 			src,
 			chars: src.char_indices().peekable(),
 			bracket_depth: 0,
-	 }
+			line_start: true,
+			param_tokens: None,
+		}
 	}
 
 	fn next_number(&mut self, i: usize, has_dot: bool) -> Spanned<Token<'i>, usize, LexerError> {
@@ -126,10 +138,23 @@ impl<'i> Lexer<'i> {
 			"iter"    => Ok((i, Token::Iter,    j)),
 			"if"      => Ok((i, Token::If,      j)),
 			"while"   => Ok((i, Token::While,   j)),
+			"param" if self.starts_param() => Ok((i, Token::Param, j)),
+			// param <name> : <kind> in
+			"in" if self.param_tokens == Some(3) => Ok((i, Token::In, j)),
 			_ => Ok((i, Token::Name(s), j)),
 		}
 	}
 
+	// whether a param just lexed begins a declaration, in which
+	// case it starts a line and is followed by a name
+	fn starts_param(&self) -> bool {
+		if !self.line_start || self.bracket_depth != 0 {
+			return false
+		}
+		let mut rest = self.chars.clone().skip_while(|(_, c)| matches!(c, ' ' | '\t'));
+		rest.next().is_some_and(|(_, c)| is_ident_begin(c))
+	}
+
 	fn skip_whitespace(&mut self) {
 		while matches!(self.chars.peek(), Some((_, ' ' | '\t' | '\n' | '\r'))) {
 			if self.bracket_depth == 0 && matches!(self.chars.peek(), Some((_, '\n'))) {
@@ -156,6 +181,8 @@ impl<'i> Lexer<'i> {
 			(i, ')') => { self.bracket_depth -= 1; Ok((i, Token::RParen, i + 1)) },
 			(i, '{') => { self.bracket_depth += 1; Ok((i, Token::LBrace, i + 1)) },
 			(i, '}') => { self.bracket_depth -= 1; Ok((i, Token::RBrace, i + 1)) },
+			(i, '[') => { self.bracket_depth += 1; Ok((i, Token::LBracket, i + 1)) },
+			(i, ']') => { self.bracket_depth -= 1; Ok((i, Token::RBracket, i + 1)) },
 
 			(i, '+') => Ok((i, Token::Plus, i + 1)),
 			(i, '-') => match self.chars.next_if(|(_, c)| *c == '>') {
@@ -203,6 +230,36 @@ impl<'i> Iterator for Lexer<'i> {
 	type Item = Spanned<Token<'i>, usize, LexerError>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		self.next_token()
+		let token = self.next_token();
+		if let Some(Ok((_, token, _))) = &token {
+			self.param_tokens = match token {
+				Token::Param => Some(0),
+				Token::Newline => None,
+				_ => self.param_tokens.map(|n| n + 1),
+			};
+			self.line_start = matches!(token, Token::Newline);
+		}
+		token
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tokens(src: &str) -> Vec<String> {
+		Lexer::new(src).map(|t| format!("{:?}", t.unwrap().1)).collect()
+	}
+
+	#[test]
+	fn param_and_in_are_contextual() {
+		assert_eq!(tokens("param a: real in [0, 1]")[..5], ["Param", "Name(\"a\")", "Colon", "Name(\"real\")", "In"]);
+		assert_eq!(tokens("  param b: point = in")[5], "Name(\"in\")");
+		assert_eq!(tokens("in = 2")[0], "Name(\"in\")");
+		assert_eq!(tokens("param = 2")[0], "Name(\"param\")");
+		assert_eq!(tokens("param(x) = x")[0], "Name(\"param\")");
+		assert_eq!(tokens("f(z) = z -> in, in")[7..], ["Name(\"in\")", "Comma", "Name(\"in\")"]);
+		assert_eq!(tokens("f(z) = z * param")[7], "Name(\"param\")");
+		assert_eq!(tokens("c = 1\nparam a: real")[4], "Param");
 	}
 }
@@ -0,0 +1,275 @@
+use num_complex::Complex64 as Complex;
+
+use super::ast::{BinaryOp, UnaryOp};
+
+// the inference pass in the compiler and code generation both go through
+// the functions here, so the type a value is given when inferring the
+// types of locals is always the type of the code later generated for it.
+
+/// What is known about a value at compile time. Real values are
+/// f32 in the generated code, complex values are vec2f
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Type {
+	/// Real and not negative, or NaN
+	NonNeg,
+	Real,
+	Complex,
+	/// An RGB color, a vec3f, made by the color builtins. Colors
+	/// are not numbers, and the compiler rejects arithmetic on them
+	Color,
+}
+
+impl Type {
+	pub fn join(self, other: Type) -> Type {
+		self.max(other)
+	}
+
+	pub fn is_real(self) -> bool {
+		self < Type::Complex
+	}
+
+	pub fn wgsl(self) -> &'static str {
+		match self {
+			Type::NonNeg | Type::Real => "f32",
+			Type::Complex => "vec2f",
+			Type::Color => "vec3f",
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Value {
+	pub code: String,
+	pub ty: Type,
+	// the value of a real literal
+	literal: Option<f64>,
+}
+
+impl Value {
+	pub fn new(code: String, ty: Type) -> Self {
+		Self { code, ty, literal: None }
+	}
+
+	pub fn number(n: Complex) -> Self {
+		if n.im != 0.0 {
+			return Self::new(format!("vec2f({:?}, {:?})", n.re, n.im), Type::Complex)
+		}
+		let (code, ty) = if n.re.is_sign_negative() {
+			(format!("({:?})", n.re), Type::Real)
+		} else {
+			(format!("{:?}", n.re), Type::NonNeg)
+		};
+		Self { code, ty, literal: Some(n.re) }
+	}
+
+	/// The value as a vec2f
+	pub fn complex(&self) -> String {
+		if self.ty.is_real() {
+			format!("vec2f({}, 0.0)", self.code)
+		} else {
+			self.code.clone()
+		}
+	}
+
+	/// The real part of the value as an f32
+	pub fn real_part(&self) -> String {
+		if self.ty.is_real() {
+			self.code.clone()
+		} else {
+			format!("{}.x", self.code)
+		}
+	}
+
+	/// The value as the representation of ty, which must
+	/// be at least as general as the value's own type
+	pub fn to(&self, ty: Type) -> String {
+		if ty.is_real() {
+			self.code.clone()
+		} else {
+			self.complex()
+		}
+	}
+
+	// the constant folder leaves operations on literals that have no finite
+	// f32 result, which naga would reject if they were written as real
+	// operations, since it evaluates those itself
+	fn unfolded(args: &[&Value]) -> Option<Vec<Value>> {
+		(!args.is_empty() && args.iter().all(|a| a.literal.is_some()))
+			.then(|| args.iter().map(|a| Value::new(a.complex(), Type::Complex)).collect())
+	}
+
+	fn integer(&self) -> Option<i32> {
+		let n = self.literal?;
+		(n.fract() == 0.0 && n.abs() <= 64.0).then_some(n as i32)
+	}
+}
+
+fn real(code: String) -> Value {
+	Value::new(code, Type::Real)
+}
+
+fn nonneg(code: String) -> Value {
+	Value::new(code, Type::NonNeg)
+}
+
+fn bool_value(cond: String) -> Value {
+	nonneg(format!("select(0.0, 1.0, {cond})"))
+}
+
+pub fn binary(op: BinaryOp, a: &Value, b: &Value) -> Value {
+	if let Some(args) = Value::unfolded(&[a, b]) {
+		return binary(op, &args[0], &args[1])
+	}
+	let both_real = a.ty.is_real() && b.ty.is_real();
+	let (ac, bc) = (&a.code, &b.code);
+	match op {
+		BinaryOp::Add if both_real => Value::new(format!("({ac} + {bc})"), a.ty.join(b.ty)),
+		BinaryOp::Sub if both_real => real(format!("({ac} - {bc})")),
+		BinaryOp::Mul if both_real => Value::new(format!("({ac} * {bc})"), a.ty.join(b.ty)),
+		BinaryOp::Div if both_real => Value::new(format!("r_div({ac}, {bc})"), a.ty.join(b.ty)),
+		// scaling a complex number by a real one is componentwise
+		BinaryOp::Mul if a.ty.is_real() || b.ty.is_real()
+			=> Value::new(format!("({ac} * {bc})"), Type::Complex),
+		BinaryOp::Div if b.ty.is_real()
+			=> Value::new(format!("c_div_real({ac}, {bc})"), Type::Complex),
+		BinaryOp::Pow => pow(a, b),
+		BinaryOp::Add => Value::new(format!("({} + {})", a.complex(), b.complex()), Type::Complex),
+		BinaryOp::Sub => Value::new(format!("({} - {})", a.complex(), b.complex()), Type::Complex),
+		BinaryOp::Mul => Value::new(format!("c_mul({ac}, {bc})"), Type::Complex),
+		BinaryOp::Div => Value::new(format!("c_div({}, {bc})", a.complex()), Type::Complex),
+		BinaryOp::Gt => bool_value(format!("{} > {}", a.real_part(), b.real_part())),
+		BinaryOp::Lt => bool_value(format!("{} < {}", a.real_part(), b.real_part())),
+		BinaryOp::Ge => bool_value(format!("{} >= {}", a.real_part(), b.real_part())),
+		BinaryOp::Le => bool_value(format!("{} <= {}", a.real_part(), b.real_part())),
+		BinaryOp::Eq if both_real => bool_value(format!("{ac} == {bc}")),
+		BinaryOp::Ne if both_real => bool_value(format!("{ac} != {bc}")),
+		BinaryOp::Eq => bool_value(format!("all({} == {})", a.complex(), b.complex())),
+		BinaryOp::Ne => bool_value(format!("any({} != {})", a.complex(), b.complex())),
+	}
+}
+
+pub fn unary(op: UnaryOp, a: &Value) -> Value {
+	match op {
+		UnaryOp::Pos => a.clone(),
+		UnaryOp::Neg if a.ty.is_real() => real(format!("(-{})", a.code)),
+		UnaryOp::Neg => Value::new(format!("(-{})", a.code), Type::Complex),
+		UnaryOp::Conj if a.ty.is_real() => a.clone(),
+		UnaryOp::Conj => Value::new(format!("c_conj({})", a.code), Type::Complex),
+	}
+}
+
+fn pow(a: &Value, b: &Value) -> Value {
+	match (a.ty, b.integer()) {
+		(Type::Complex, _) => (),
+		(_, Some(n)) => {
+			let ty = if n % 2 == 0 { Type::NonNeg } else { a.ty };
+			return Value::new(format!("r_powi({}, {n})", a.code), ty)
+		}
+		(Type::NonNeg, None) if b.ty.is_real() => return nonneg(format!("pow({}, {})", a.code, b.code)),
+		_ => (),
+	}
+	Value::new(format!("c_pow({}, {})", a.complex(), b.complex()), Type::Complex)
+}
+
+/// A call to the builtin with WGSL name func
+pub fn call(func: &str, args: &[Value]) -> Value {
+	if let Some(v) = call_real_args(func, args) {
+		return v
+	}
+	if let Some(args) = Value::unfolded(&args.iter().collect::<Vec<_>>()) {
+		return call(func, &args)
+	}
+	if args.iter().all(|a| a.ty.is_real()) {
+		if let Some(v) = call_real(func, args) {
+			return v
+		}
+	}
+	if let Some(v) = call_real_valued(func, args) {
+		return v
+	}
+	let args: Vec<String> = args.iter().map(Value::complex).collect();
+	Value::new(format!("{func}({})", args.join(", ")), Type::Complex)
+}
+
+// builtins applied to real arguments that have a real result
+fn call_real(func: &str, args: &[Value]) -> Option<Value> {
+	let a = |i: usize| &args[i].code;
+	let ty = |i: usize| args[i].ty;
+	let value = match (func, args.len()) {
+		("c_pos" | "c_re" | "c_conj", 1) => args[0].clone(),
+		("c_neg", 1) => unary(UnaryOp::Neg, &args[0]),
+		("c_recip", 1) => Value::new(format!("r_div(1.0, {})", a(0)), ty(0)),
+		("c_im" | "c_signim" | "c_absim", 1) => nonneg("0.0".to_owned()),
+		("c_signre" | "c_sign", 1) => real(format!("sign({})", a(0))),
+		("c_absre" | "c_abs", 1) => nonneg(format!("abs({})", a(0))),
+		("c_abs_sq", 1) => nonneg(format!("r_powi({}, 2)", a(0))),
+		("c_arg", 1) => nonneg(format!("r_arg({})", a(0))),
+		("c_floor", 1) => Value::new(format!("floor({})", a(0)), ty(0)),
+		("c_ceil", 1) => Value::new(format!("ceil({})", a(0)), ty(0)),
+		("c_round", 1) => Value::new(format!("round({})", a(0)), ty(0)),
+		("c_frac", 1) => nonneg(format!("fract({})", a(0))),
+		("c_diveu", 2) => real(format!("floor(r_div({}, {}))", a(0), a(1))),
+		("c_mod", 2) => real(format!("r_mod({}, {})", a(0), a(1))),
+
+		("c_add", 2) => binary(BinaryOp::Add, &args[0], &args[1]),
+		("c_sub", 2) => binary(BinaryOp::Sub, &args[0], &args[1]),
+		("c_mul", 2) => binary(BinaryOp::Mul, &args[0], &args[1]),
+		("c_div", 2) => binary(BinaryOp::Div, &args[0], &args[1]),
+		("c_pow", 2) => {
+			let v = pow(&args[0], &args[1]);
+			if !v.ty.is_real() {
+				return None
+			}
+			v
+		}
+
+		("c_exp", 1) => nonneg(format!("exp({})", a(0))),
+		("c_log", 1) if ty(0) == Type::NonNeg => real(format!("log({})", a(0))),
+		("c_log2", 1) if ty(0) == Type::NonNeg => real(format!("log2({})", a(0))),
+		("c_log10", 1) if ty(0) == Type::NonNeg => real(format!("(log({}) / LOG_10)", a(0))),
+		("c_sqrt", 1) if ty(0) == Type::NonNeg => nonneg(format!("sqrt({})", a(0))),
+		("c_sin" | "c_cos" | "c_tan" | "c_sinh" | "c_tanh" | "c_atan" | "c_asinh", 1)
+			=> real(format!("{}({})", &func[2..], a(0))),
+		("c_cosh", 1) => nonneg(format!("cosh({})", a(0))),
+
+		("c_min", 2) => Value::new(format!("r_min({}, {})", a(0), a(1)), ty(0).join(ty(1))),
+		("c_max", 2) => Value::new(format!("r_max({}, {})", a(0), a(1)), ty(0).join(ty(1))),
+		("c_clamp", 3) => Value::new(format!("r_min(r_max({}, {}), {})", a(0), a(1), a(2)),
+			ty(0).join(ty(1)).join(ty(2))),
+		("c_atan2", 2) => real(format!("r_atan2({}, {})", a(0), a(1))),
+		("c_hypot", 2) => nonneg(format!("length(vec2f({}, {}))", a(0), a(1))),
+		("c_step", 2) => nonneg(format!("step({}, {})", a(0), a(1))),
+		("c_smoothstep", 3) => nonneg(format!("r_smoothstep({}, {}, {})", a(0), a(1), a(2))),
+		("c_mix", 3) => real(format!("mix({}, {}, {})", a(0), a(1), a(2))),
+		_ => return None,
+	};
+	Some(value)
+}
+
+// builtins that only take the real parts of their arguments
+fn call_real_args(func: &str, args: &[Value]) -> Option<Value> {
+	let ty = match (func, args.len()) {
+		("c_rgb" | "c_hsv" | "c_oklch", 3) => Type::Color,
+		("c_contour", 2) => Type::Real,
+		("c_isoline", 2) => Type::NonNeg,
+		_ => return None,
+	};
+	let args: Vec<String> = args.iter().map(Value::real_part).collect();
+	Some(Value::new(format!("{func}({})", args.join(", ")), ty))
+}
+
+// builtins whose result is always real
+fn call_real_valued(func: &str, args: &[Value]) -> Option<Value> {
+	let ty = match (func, args.len()) {
+		("c_re", 1) => return Some(real(args[0].real_part())),
+		("c_im", 1) => return Some(real(format!("{}.y", args[0].complex()))),
+		("c_signre" | "c_signim" | "c_arg" | "c_noise", 1) => Type::Real,
+		("c_argbr" | "c_atan2", 2) => Type::Real,
+		("c_absre" | "c_absim" | "c_isnan" | "c_abs_sq" | "c_abs", 1) => Type::NonNeg,
+		("c_hypot" | "c_step" | "c_hdist" | "c_rand", 2) => Type::NonNeg,
+		("c_smoothstep", 3) => Type::NonNeg,
+		_ => return None,
+	};
+	let args: Vec<String> = args.iter().map(Value::complex).collect();
+	Some(Value::new(format!("{func}({}).x", args.join(", ")), ty))
+}
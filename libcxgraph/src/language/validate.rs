@@ -0,0 +1,108 @@
+use std::error::Error;
+
+use naga::{front::wgsl, valid::{Capabilities, ValidationFlags, Validator}, Module, SourceLocation};
+
+use super::{ast::Span, builtins::BUILTIN_FUNCS, compiler::{source_name, CompileError}};
+
+/// Check the fragment shader with the generated definitions appended,
+/// so that mistakes in code generation are reported as compile errors
+/// at the source that produced them rather than when the pipeline is
+/// created. `line_spans` has the source span of each line of `userdefs`
+pub fn validate(fragment_src: &str, userdefs: &str, line_spans: &[Span]) -> Result<(), CompileError> {
+	let src = format!("{fragment_src}{userdefs}");
+	let header_lines = fragment_src.lines().count();
+	let module = wgsl::parse_str(&src)
+		.map_err(|e| shader_error(reword(e.message(), None), e.location(&src), header_lines, line_spans))?;
+	Validator::new(ValidationFlags::all(), Capabilities::default())
+		.validate(&module)
+		.map_err(|e| {
+			// the outer errors only say which function or expression is invalid
+			let mut msg = e.to_string();
+			let mut source = e.source();
+			while let Some(inner) = source {
+				msg = format!("{msg}: {inner}");
+				source = inner.source();
+			}
+			shader_error(reword(&msg, Some(&module)), e.location(&src), header_lines, line_spans)
+		})?;
+	Ok(())
+}
+
+fn shader_error(msg: String, location: Option<SourceLocation>, header_lines: usize, line_spans: &[Span]) -> CompileError {
+	let span = location
+		.and_then(|loc| (loc.line_number as usize - 1).checked_sub(header_lines))
+		.and_then(|line| line_spans.get(line).copied());
+	CompileError::new(format!("generated shader is invalid: {msg}"), span)
+}
+
+// naga refers to functions and expressions by handle and to everything
+// by its generated name, neither of which mean anything in the source.
+// calls are given the name of the function called, other handles are
+// left out and generated names are turned back into source names
+fn reword(msg: &str, module: Option<&Module>) -> String {
+	let mut result = String::with_capacity(msg.len());
+	let mut chars = msg.char_indices().peekable();
+	while let Some((i, c)) = chars.next() {
+		if c == '[' {
+			let digits = msg[i+1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(msg.len() - i - 1);
+			let handle = msg[i+1..i+1+digits].parse::<usize>();
+			if let (Ok(handle), true) = (handle, msg[i+1+digits..].starts_with(']')) {
+				for _ in 0..=digits {
+					chars.next();
+				}
+				let func = module.filter(|_| result.ends_with("Call to "))
+					.and_then(|m| m.functions.iter().nth(handle))
+					.and_then(|(_, f)| f.name.as_deref());
+				match func {
+					Some(name) => result.push_str(&format!("`{}`", display_name(name))),
+					None => result.truncate(result.trim_end().len()),
+				}
+				continue
+			}
+		}
+		if c.is_alphanumeric() || c == '_' {
+			let len = msg[i..].find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(msg.len() - i);
+			for _ in 1..len {
+				chars.next();
+			}
+			result.push_str(&display_name(&msg[i..i+len]));
+			continue
+		}
+		result.push(c);
+	}
+	result.replace(" Some()", "")
+}
+
+fn display_name(ident: &str) -> String {
+	if let Some(name) = source_name(ident) {
+		return name
+	}
+	// builtins may have several names, so take the first for consistency
+	BUILTIN_FUNCS.with(|funcs| funcs.iter()
+		.filter(|(_, (func, _))| *func == ident)
+		.map(|(name, _)| *name)
+		.min()
+		.unwrap_or(ident)
+		.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(src: &str) -> String {
+		let lines = vec![Span { start: 0, end: 0 }; src.lines().count()];
+		validate("", src, &lines).unwrap_err().to_string()
+	}
+
+	#[test]
+	fn errors_use_source_names() {
+		let msg = error("fn c_cbrt(z: vec2f, br: vec2f) -> vec2f { return z; }\n\
+			fn func_f(arg_x: vec2f) -> vec2f { var local_x = arg_x; return c_cbrt(local_x); }");
+		assert!(msg.contains("Function 'f' is invalid: Call to `cbrt` is invalid"), "{msg}");
+		let msg = error("fn func_gu_s1_(arg_x: vec2f) -> f32 { var local_x = arg_x; return local_x; }");
+		assert!(msg.contains("Function 'g_₁' is invalid: The `return` value does not match"), "{msg}");
+		let msg = error("fn func_f(arg_x: vec2f) -> vec2f { return arg_x * const_ap_; }");
+		assert!(msg.contains("identifier: 'a''"), "{msg}");
+	}
+}
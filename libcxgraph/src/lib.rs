@@ -1,2 +1,5 @@
 pub mod language;
 pub mod renderer;
+pub mod timeline;
+pub mod session;
+pub mod link;
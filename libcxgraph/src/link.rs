@@ -0,0 +1,454 @@
+use std::io::{Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::session::{Session, SessionError, Settings, View, Variable, SESSION_VERSION};
+
+/// The version of the link format written by `encode`
+pub const LINK_VERSION: u8 = 2;
+
+// the most bytes a link may decompress to, so that a small link
+// cannot make decoding use an unbounded amount of memory
+const MAX_DECODED_LEN: u64 = 1 << 20;
+
+const FLAG_MOBIUS: u8 = 1;
+const FLAG_SETTINGS: u8 = 2;
+const FLAGS_ALL: u8 = FLAG_MOBIUS | FLAG_SETTINGS;
+
+const KIND_SLIDER: u8 = 0;
+const KIND_POINT: u8 = 1;
+
+//
+// The link is base64url without padding. Its first byte is the version,
+// then the rest is deflated:
+//   flags: u8, saying whether the Möbius transformation and settings follow
+//   source: string
+//   bounds_min, bounds_max: 2 f64 each
+//   mobius: 8 f64, if FLAG_MOBIUS
+//   res_scale, shading_intensity, contour_intensity: f64, if FLAG_SETTINGS
+//   decorations, coloring, grid_mode, iteration_budget: varint, if FLAG_SETTINGS
+//   modulus_base: f64 then phase_sectors: varint, if FLAG_SETTINGS and not version 1
+//   variable count: varint
+//   each variable: kind u8, name string, then
+//     value, min, max, step: f64 for a slider
+//     value: 2 f64 for a point
+// strings are a varint length followed by UTF-8, f64s are little-endian
+// and varints are LEB128
+//
+
+/// Encode the program, view, settings and variables of a session as text
+/// that can go in a URL. The timeline is not included
+pub fn encode(session: &Session) -> String {
+	let mut flags = 0;
+	if session.view.mobius != View::IDENTITY_MOBIUS {
+		flags |= FLAG_MOBIUS;
+	}
+	if session.settings != Settings::default() {
+		flags |= FLAG_SETTINGS;
+	}
+
+	let mut buf = Vec::new();
+	buf.push(flags);
+	write_str(&mut buf, &session.source);
+	write_f64s(&mut buf, &session.view.bounds_min);
+	write_f64s(&mut buf, &session.view.bounds_max);
+	if flags & FLAG_MOBIUS != 0 {
+		write_f64s(&mut buf, &session.view.mobius);
+	}
+	if flags & FLAG_SETTINGS != 0 {
+		let s = &session.settings;
+		write_f64s(&mut buf, &[s.res_scale, s.shading_intensity, s.contour_intensity]);
+		for n in [s.decorations, s.coloring, s.grid_mode, s.iteration_budget] {
+			write_varint(&mut buf, n as u64);
+		}
+		write_f64s(&mut buf, &[s.modulus_base]);
+		write_varint(&mut buf, s.phase_sectors as u64);
+	}
+	write_varint(&mut buf, session.variables.len() as u64);
+	for var in &session.variables {
+		match var {
+			Variable::Slider { name, value, min, max, step } => {
+				buf.push(KIND_SLIDER);
+				write_str(&mut buf, name);
+				write_f64s(&mut buf, &[*value, *min, *max, *step]);
+			}
+			Variable::Point { name, value } => {
+				buf.push(KIND_POINT);
+				write_str(&mut buf, name);
+				write_f64s(&mut buf, value);
+			}
+		}
+	}
+
+	let mut encoder = DeflateEncoder::new(vec![LINK_VERSION], Compression::best());
+	encoder.write_all(&buf).expect("writing to a Vec cannot fail");
+	let bytes = encoder.finish().expect("writing to a Vec cannot fail");
+	URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Decode a session from text made by `encode`. Links from a newer
+/// version are rejected, as is anything that was not made by `encode`
+pub fn decode(link: &str) -> Result<Session, SessionError> {
+	let bytes = URL_SAFE_NO_PAD.decode(link.trim())
+		.map_err(|e| invalid(format!("link is not base64url: {e}")))?;
+	let (&version, deflated) = bytes.split_first().ok_or_else(|| invalid("link is empty"))?;
+	if version == 0 {
+		return Err(invalid("link version 0 does not exist"))
+	}
+	if version > LINK_VERSION {
+		return Err(SessionError::NewerLinkVersion(version))
+	}
+
+	let mut buf = Vec::new();
+	DeflateDecoder::new(deflated)
+		.take(MAX_DECODED_LEN + 1)
+		.read_to_end(&mut buf)
+		.map_err(|e| invalid(format!("link is corrupt: {e}")))?;
+	if buf.len() as u64 > MAX_DECODED_LEN {
+		return Err(invalid("link is too large"))
+	}
+
+	let mut r = Reader { buf: &buf };
+	let flags = r.u8()?;
+	if flags & !FLAGS_ALL != 0 {
+		return Err(invalid(format!("unknown flags {flags:#x}")))
+	}
+	let source = r.string()?;
+	let bounds_min = r.f64s()?;
+	let bounds_max = r.f64s()?;
+	let mobius = if flags & FLAG_MOBIUS != 0 { r.f64s()? } else { View::IDENTITY_MOBIUS };
+	let settings = if flags & FLAG_SETTINGS != 0 {
+		let [res_scale, shading_intensity, contour_intensity] = r.f64s()?;
+		let mut settings = Settings {
+			res_scale,
+			shading_intensity,
+			contour_intensity,
+			decorations: r.u32()?,
+			coloring: r.u32()?,
+			grid_mode: r.u32()?,
+			iteration_budget: r.u32()?,
+			..Settings::default()
+		};
+		if version >= 2 {
+			[settings.modulus_base] = r.f64s()?;
+			settings.phase_sectors = r.u32()?;
+		}
+		settings
+	} else {
+		Settings::default()
+	};
+	let count = r.varint()?;
+	let mut variables = Vec::new();
+	for _ in 0..count {
+		let var = match r.u8()? {
+			KIND_SLIDER => {
+				let name = r.string()?;
+				let [value, min, max, step] = r.f64s()?;
+				Variable::Slider { name, value, min, max, step }
+			}
+			KIND_POINT => Variable::Point { name: r.string()?, value: r.f64s()? },
+			kind => return Err(invalid(format!("unknown variable kind {kind}"))),
+		};
+		variables.push(var);
+	}
+	if !r.buf.is_empty() {
+		return Err(invalid("link has trailing data"))
+	}
+
+	Ok(Session {
+		version: SESSION_VERSION,
+		source,
+		view: View { bounds_min, bounds_max, mobius },
+		settings,
+		variables,
+		timeline: None,
+	})
+}
+
+fn invalid(msg: impl Into<String>) -> SessionError {
+	SessionError::Invalid(msg.into())
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+	while n >= 0x80 {
+		buf.push(n as u8 | 0x80);
+		n >>= 7;
+	}
+	buf.push(n as u8);
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+	write_varint(buf, s.len() as u64);
+	buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_f64s(buf: &mut Vec<u8>, xs: &[f64]) {
+	for x in xs {
+		buf.extend_from_slice(&x.to_le_bytes());
+	}
+}
+
+struct Reader<'a> {
+	buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, n: usize) -> Result<&'a [u8], SessionError> {
+		if n > self.buf.len() {
+			return Err(invalid("link ends unexpectedly"))
+		}
+		let (bytes, rest) = self.buf.split_at(n);
+		self.buf = rest;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> Result<u8, SessionError> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn varint(&mut self) -> Result<u64, SessionError> {
+		let mut n = 0u64;
+		for shift in (0..64).step_by(7) {
+			let byte = self.u8()?;
+			let bits = (byte & 0x7f) as u64;
+			if bits << shift >> shift != bits {
+				break
+			}
+			n |= bits << shift;
+			if byte & 0x80 == 0 {
+				return Ok(n)
+			}
+		}
+		Err(invalid("number is too large"))
+	}
+
+	fn u32(&mut self) -> Result<u32, SessionError> {
+		u32::try_from(self.varint()?).map_err(|_| invalid("number is too large"))
+	}
+
+	fn string(&mut self) -> Result<String, SessionError> {
+		let len = usize::try_from(self.varint()?).map_err(|_| invalid("string is too long"))?;
+		let bytes = self.bytes(len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string is not valid UTF-8"))
+	}
+
+	// non-finite numbers are never useful in a session, so they are rejected
+	// rather than being allowed to reach the renderer
+	fn f64s<const N: usize>(&mut self) -> Result<[f64; N], SessionError> {
+		let mut xs = [0.0; N];
+		for x in &mut xs {
+			let bytes = self.bytes(8)?.try_into().expect("slice has length 8");
+			*x = f64::from_le_bytes(bytes);
+			if !x.is_finite() {
+				return Err(invalid("number is not finite"))
+			}
+		}
+		Ok(xs)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn session() -> Session {
+		Session::new("plot(z) = z^2 - c".to_owned(), [-2.0, -1.5], [2.0, 1.5])
+	}
+
+	// a link with the given version byte and uncompressed payload
+	fn link(version: u8, payload: &[u8]) -> String {
+		let mut encoder = DeflateEncoder::new(vec![version], Compression::best());
+		encoder.write_all(payload).unwrap();
+		URL_SAFE_NO_PAD.encode(encoder.finish().unwrap())
+	}
+
+	// the payload of a session with no Möbius transformation, settings or
+	// variables, with the given source
+	fn payload(source: &[u8]) -> Vec<u8> {
+		let mut buf = vec![0];
+		write_varint(&mut buf, source.len() as u64);
+		buf.extend_from_slice(source);
+		write_f64s(&mut buf, &[-1.0, -1.0, 1.0, 1.0]);
+		buf
+	}
+
+	fn assert_invalid(link: &str) {
+		match decode(link) {
+			Err(SessionError::Invalid(_)) => (),
+			result => panic!("expected an invalid link, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn round_trip_plain() {
+		let session = session();
+		assert_eq!(decode(&encode(&session)).unwrap(), session);
+	}
+
+	#[test]
+	fn round_trip_mobius_settings_and_variables() {
+		let mut session = session();
+		session.view.mobius = [0.0, 1.0, 2.0, 0.0, -1.0, 0.5, 1.0, 0.0];
+		session.settings.coloring = 7;
+		session.settings.decorations = 5;
+		session.settings.contour_intensity = 0.25;
+		session.settings.modulus_base = 1.5;
+		session.settings.phase_sectors = 12;
+		session.settings.iteration_budget = 1 << 20;
+		session.variables = vec![
+			Variable::Slider { name: "a".to_owned(), value: 0.5, min: -2.0, max: 2.0, step: 0.01 },
+			Variable::Point { name: "c".to_owned(), value: [0.3, -0.5] },
+		];
+		assert_eq!(decode(&encode(&session)).unwrap(), session);
+	}
+
+	#[test]
+	fn round_trip_each_flag() {
+		let mut mobius = session();
+		mobius.view.mobius[2] = 1.0;
+		let mut settings = session();
+		settings.settings.grid_mode = 2;
+		for session in [mobius, settings] {
+			assert_eq!(decode(&encode(&session)).unwrap(), session);
+		}
+	}
+
+	#[test]
+	fn round_trip_unicode_source() {
+		let mut session = session();
+		session.source = "plot(z) = Γ(z) + ψ(z)".to_owned();
+		assert_eq!(decode(&encode(&session)).unwrap(), session);
+	}
+
+	#[test]
+	fn version_zero() {
+		assert_invalid(&link(0, &payload(b"plot(z) = z")));
+	}
+
+	#[test]
+	fn newer_version() {
+		let result = decode(&link(LINK_VERSION + 1, &payload(b"plot(z) = z")));
+		assert!(matches!(result, Err(SessionError::NewerLinkVersion(v)) if v == LINK_VERSION + 1));
+		let msg = format!("link has version {}, but only versions up to {LINK_VERSION} are supported", LINK_VERSION + 1);
+		assert_eq!(result.unwrap_err().to_string(), msg);
+	}
+
+	#[test]
+	fn version_1_settings() {
+		// version 1 settings have no modulus base or phase sectors
+		let mut buf = payload(b"plot(z) = z");
+		buf[0] = FLAG_SETTINGS;
+		write_f64s(&mut buf, &[2.0, 0.5, 0.25]);
+		for n in [3, 7, 1, 1000] {
+			write_varint(&mut buf, n);
+		}
+		write_varint(&mut buf, 0);
+
+		let session = decode(&link(1, &buf)).unwrap();
+		let expected = Settings {
+			res_scale: 2.0,
+			shading_intensity: 0.5,
+			contour_intensity: 0.25,
+			decorations: 3,
+			coloring: 7,
+			grid_mode: 1,
+			iteration_budget: 1000,
+			..Settings::default()
+		};
+		assert_eq!(session.settings, expected);
+		assert_eq!(session.source, "plot(z) = z");
+	}
+
+	#[test]
+	fn version_1_without_settings() {
+		let mut buf = payload(b"plot(z) = z");
+		write_varint(&mut buf, 0);
+		let session = decode(&link(1, &buf)).unwrap();
+		assert_eq!(session.settings, Settings::default());
+		assert_eq!(session.view.bounds_max, [1.0, 1.0]);
+	}
+
+	#[test]
+	fn not_base64() {
+		assert_invalid("not a link!");
+		assert_invalid("");
+	}
+
+	#[test]
+	fn truncated() {
+		let text = encode(&session());
+		let bytes = URL_SAFE_NO_PAD.decode(&text).unwrap();
+		for len in 1..bytes.len() {
+			assert_invalid(&URL_SAFE_NO_PAD.encode(&bytes[..len]));
+		}
+		// complete deflate streams whose payloads stop early
+		let mut buf = payload(b"plot(z) = z");
+		write_varint(&mut buf, 0);
+		for len in 0..buf.len() {
+			assert_invalid(&link(LINK_VERSION, &buf[..len]));
+		}
+	}
+
+	#[test]
+	fn trailing_data() {
+		let mut buf = payload(b"plot(z) = z");
+		write_varint(&mut buf, 0);
+		buf.push(0);
+		assert_invalid(&link(LINK_VERSION, &buf));
+	}
+
+	#[test]
+	fn unknown_flags_and_kinds() {
+		let mut buf = payload(b"plot(z) = z");
+		write_varint(&mut buf, 0);
+		buf[0] = 0x80;
+		assert_invalid(&link(LINK_VERSION, &buf));
+
+		let mut buf = payload(b"plot(z) = z");
+		write_varint(&mut buf, 1);
+		buf.push(7);
+		write_str(&mut buf, "a");
+		assert_invalid(&link(LINK_VERSION, &buf));
+	}
+
+	#[test]
+	fn bad_utf8() {
+		let mut buf = payload(b"plot(z) = \xff");
+		write_varint(&mut buf, 0);
+		assert_invalid(&link(LINK_VERSION, &buf));
+	}
+
+	#[test]
+	fn non_finite() {
+		for x in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+			let mut buf = vec![0];
+			write_str(&mut buf, "plot(z) = z");
+			write_f64s(&mut buf, &[-1.0, x, 1.0, 1.0]);
+			write_varint(&mut buf, 0);
+			assert_invalid(&link(LINK_VERSION, &buf));
+		}
+	}
+
+	#[test]
+	fn varint_overflow() {
+		let mut buf = payload(b"plot(z) = z");
+		buf.extend_from_slice(&[0xff; 11]);
+		buf.push(0);
+		assert_invalid(&link(LINK_VERSION, &buf));
+	}
+
+	#[test]
+	fn oversized() {
+		// a long run of zeros deflates to almost nothing
+		let buf = vec![0; MAX_DECODED_LEN as usize + 1];
+		let text = link(LINK_VERSION, &buf);
+		assert!(text.len() < 4096);
+		assert_invalid(&text);
+
+		// a source that claims to be longer than the link
+		let mut buf = vec![0];
+		write_varint(&mut buf, u64::MAX);
+		assert_invalid(&link(LINK_VERSION, &buf));
+	}
+}
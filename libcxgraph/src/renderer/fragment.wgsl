@@ -2,21 +2,14 @@
 //  uniforms  //
 ////////////////
 
-struct Uniforms {
-	variables: array<vec4f, 4>,
-	resolution: vec2u,
-	bounds_min: vec2f,
-	bounds_max: vec2f,
-	res_scale: f32,
-	shading_intensity: f32,
-	contour_intensity: f32,
-	decoration: u32,
-	coloring: u32,
-	grid_mode: u32,
-}
+// struct Uniforms is generated from the fields of Uniforms
+// in renderer/mod.rs, followed by any custom uniforms
 
 @group(0) @binding(1) var<uniform> uniforms: Uniforms;
 
+// the user variables are at binding 2, declared by the compiled
+// program as an array sized for the variables it uses
+
 /////////////////
 //  constants  //
 /////////////////
@@ -28,6 +21,8 @@ const LOG_TAU = 1.8378770664093453;
 const LOG_2 = 0.6931471805599453;
 const LOG_10 = 2.302585092994046;
 const RECIP_SQRT29 = 0.18569533817705186;
+const SQRT_PI = 1.7724538509055159;
+const RECIP_SQRT_PI = 0.5641895835477563;
 
 const C_TAU = vec2f(TAU, 0.0);
 const C_E = vec2f(E, 0.0);
@@ -50,6 +45,13 @@ fn screen2cx(pos: vec2f) -> vec2f {
 	return remap(pos, C_ZERO, vec2f(uniforms.resolution), uniforms.bounds_min, uniforms.bounds_max);
 }
 
+// map the viewport through the mobius transformation
+// given by the coefficients in uniforms.mobius
+fn mobius_view(z: vec2f) -> vec2f {
+	let m = uniforms.mobius;
+	return c_mobius(m[0].xy, m[0].zw, m[1].xy, m[1].zw, z);
+}
+
 fn cx2screen(z: vec2f) -> vec2f {
 	return remap(z, uniforms.bounds_min, uniforms.bounds_max, C_ZERO, vec2f(uniforms.resolution));
 }
@@ -74,6 +76,93 @@ fn vlength(v: vec2f) -> f32 {
 	return max(max(a, b), max(c, d));
 }
 
+// loops in compiled programs call use_iteration before each iteration and
+// stop once uniforms.iteration_budget iterations have run for the pixel,
+// which is then drawn in BUDGET_EXCEEDED_COLOR in place of its value
+
+const BUDGET_EXCEEDED_COLOR = vec3f(1.0, 0.0, 1.0);
+
+var<private> iterations_left: u32;
+var<private> budget_exceeded: bool;
+
+fn use_iteration() -> bool {
+	if iterations_left == 0u {
+		budget_exceeded = true;
+		return false;
+	}
+	iterations_left -= 1u;
+	return true;
+}
+
+//////////////////////
+//  real functions  //
+//////////////////////
+
+// versions of complex functions for values the compiler
+// knows are real, matching them on the real axis
+
+fn r_powi(x: f32, n: i32) -> f32 {
+	var base = x;
+	if n < 0 {
+		base = 1.0 / x;
+	}
+	var k = abs(n);
+	var result = 1.0;
+	loop {
+		if (k & 1) != 0 {
+			result *= base;
+		}
+		k >>= 1u;
+		if k == 0 {
+			break;
+		}
+		base *= base;
+	}
+	return result;
+}
+
+// division as c_div does it, giving NaN rather than an infinity
+// when dividing by zero
+fn r_div(x: f32, y: f32) -> f32 {
+	return x * y / (y * y);
+}
+
+fn c_div_real(u: vec2f, y: f32) -> vec2f {
+	return u * y / (y * y);
+}
+
+fn r_arg(x: f32) -> f32 {
+	return select(0.0, TAU/2.0, x < 0.0);
+}
+
+// atan2 is indeterminate at the origin, where this gives 0
+fn r_atan2(y: f32, x: f32) -> f32 {
+	if y == 0.0 && x == 0.0 {
+		return 0.0;
+	}
+	return atan2(y, x);
+}
+
+// smoothstep is indeterminate when the edges are equal, where this steps
+fn r_smoothstep(low: f32, high: f32, x: f32) -> f32 {
+	if low == high {
+		return step(low, x);
+	}
+	return smoothstep(low, high, x);
+}
+
+fn r_mod(u: f32, v: f32) -> f32 {
+	return u - floor(u / v) * v;
+}
+
+fn r_min(u: f32, v: f32) -> f32 {
+	return select(u, v, v < u);
+}
+
+fn r_max(u: f32, v: f32) -> f32 {
+	return select(u, v, v > u);
+}
+
 /////////////////////////
 //  complex functions  //
 /////////////////////////
@@ -118,9 +207,10 @@ fn c_abs(z: vec2f) -> vec2f {
 	return vec2(vlength(z), 0.0);
 }
 
+// the argument of zero is taken to be 0
 fn c_arg(z: vec2f) -> vec2f {
-	if z.x < 0.0 && z.y == 0.0 {
-		return vec2(TAU/2.0, 0.0);
+	if z.y == 0.0 {
+		return vec2(r_arg(z.x), 0.0);
 	}
 	return vec2(atan2(z.y, z.x), 0.0);
 }
@@ -129,6 +219,9 @@ fn c_argbr(z: vec2f, br: vec2f) -> vec2f {
 	if z.x < 0.0 && z.y == 0.0 {
 		return vec2(TAU/2.0 + floor(br.x/TAU) * TAU, 0.0);
 	}
+	if z.x == 0.0 && z.y == 0.0 {
+		return vec2(br.x, 0.0);
+	}
 	let r = vec2(cos(-br.x), sin(-br.x));
 	let zr = c_mul(z, r);
 	return vec2(br.x + atan2(zr.y, zr.x), 0.0);
@@ -200,11 +293,22 @@ fn c_logbr(z: vec2f, br: vec2f) -> vec2f {
 	return vec2(0.5 * log(dot(z, z)), c_argbr(z, br).x);
 }
 
+// zero to a power, where the logarithm is infinite
+fn c_pow_zero(v: vec2f) -> vec2f {
+	return vec2(select(0.0, 1.0, v.x == 0.0 && v.y == 0.0), 0.0);
+}
+
 fn c_pow(u: vec2f, v: vec2f) -> vec2f {
+	if u.x == 0.0 && u.y == 0.0 && v.x >= 0.0 {
+		return c_pow_zero(v);
+	}
 	return c_exp(c_mul(c_log(u), v));
 }
 
 fn c_powbr(u: vec2f, v: vec2f, br: vec2f) -> vec2f {
+	if u.x == 0.0 && u.y == 0.0 && v.x >= 0.0 {
+		return c_pow_zero(v);
+	}
 	return c_exp(c_mul(c_logbr(u, br), v));
 }
 
@@ -216,7 +320,7 @@ fn c_sqrtbr(z: vec2f, br: vec2f) -> vec2f {
 	return c_powbr(z, vec2(0.5, 0.0), br);
 }
 
-fn c_cbrt(z: vec2f, br: vec2f) -> vec2f {
+fn c_cbrt(z: vec2f) -> vec2f {
 	return c_pow(z, vec2(1.0/3.0, 0.0));
 }
 
@@ -401,34 +505,633 @@ fn c_lambertw_init(z: vec2f, br: f32) -> vec2f {
 	}
 }
 
+// faddeeva and error functions //
+
+// weideman's rational approximation with 24 terms, valid for im(z) >= 0
+const FADDEEVA_L = 4.119534287814236;
+fn c_faddeeva_upper(z: vec2f) -> vec2f {
+	var coeffs = array<f32, 24>(
+		-1.513746129e-10, 4.904820490e-09, 1.331045442e-09, -3.008282348e-08,
+		-1.912225889e-08, 1.873834345e-07, 2.568264134e-07, -1.085647580e-06,
+		-3.038893184e-06, 4.139461724e-06, 3.047106608e-05, 2.433141546e-05,
+		-2.074843151e-04, -7.816642996e-04, -4.936426901e-04, 6.215006363e-03,
+		3.372336686e-02, 1.083872348e-01, 2.654963960e-01, 5.361139536e-01,
+		9.257087139e-01, 1.394819673e+00, 1.856286499e+00, 2.197858937e+00,
+	);
+	let iz = vec2(-z.y, z.x);
+	let r = c_recip(vec2(FADDEEVA_L, 0.0) - iz);
+	let zz = c_mul(vec2(FADDEEVA_L, 0.0) + iz, r);
+	var p = C_ZERO;
+	for(var k = 0; k < 24; k++) {
+		p = c_mul(p, zz) + vec2(coeffs[k], 0.0);
+	}
+	return 2.0 * c_mul(p, c_mul(r, r)) + RECIP_SQRT_PI * r;
+}
+
+fn c_faddeeva(z: vec2f) -> vec2f {
+	if z.y >= 0.0 {
+		return c_faddeeva_upper(z);
+	} else {
+		return 2.0 * c_exp(-c_mul(z, z)) - c_faddeeva_upper(-z);
+	}
+}
+
+// erfc(z) for re(z) >= 0
+fn c_erfc_plus(z: vec2f) -> vec2f {
+	return c_mul(c_exp(-c_mul(z, z)), c_faddeeva_upper(vec2(-z.y, z.x)));
+}
+
+fn c_erfc(z: vec2f) -> vec2f {
+	if z.x >= 0.0 {
+		return c_erfc_plus(z);
+	} else {
+		return 2.0 * C_ONE - c_erfc_plus(-z);
+	}
+}
+
 fn c_erf(z: vec2f) -> vec2f {
-	if z.x >= 0 {
-		return c_erf_plus(z);
+	if dot(z, z) < 0.25 {
+		// 2/sqrt(pi) sum (-1)^n z^(2n+1) / (n! (2n+1))
+		let z2 = -c_mul(z, z);
+		var term = z;
+		var sum = z;
+		for(var n = 1; n < 10; n++) {
+			let nf = f32(n);
+			term = c_mul(term, z2) / nf;
+			sum += term / (2.0 * nf + 1.0);
+		}
+		return 2.0 * RECIP_SQRT_PI * sum;
+	}
+	if z.x >= 0.0 {
+		return C_ONE - c_erfc_plus(z);
+	} else {
+		return c_erfc_plus(-z) - C_ONE;
+	}
+}
+
+fn c_erfi(z: vec2f) -> vec2f {
+	let w = c_erf(vec2(-z.y, z.x));
+	return vec2(w.y, -w.x);
+}
+
+fn c_dawson(z: vec2f) -> vec2f {
+	if dot(z, z) < 0.25 {
+		// sum (-2)^n z^(2n+1) / (2n+1)!!
+		let z2 = -2.0 * c_mul(z, z);
+		var term = z;
+		var sum = z;
+		for(var n = 1; n < 10; n++) {
+			term = c_mul(term, z2) / (2.0 * f32(n) + 1.0);
+			sum += term;
+		}
+		return sum;
+	}
+	// dawson is odd, reflect to avoid cancellation in the lower half-plane
+	let m = select(1.0, -1.0, z.y < 0.0);
+	let zp = m * z;
+	let w = c_exp(-c_mul(zp, zp)) - c_faddeeva_upper(zp);
+	return m * 0.5 * SQRT_PI * vec2(-w.y, w.x);
+}
+
+// fresnel integrals //
+
+fn c_fresnels(z: vec2f) -> vec2f {
+	if dot(z, z) < 1.0 {
+		// sum (-1)^n (pi/2)^(2n+1) z^(4n+3) / ((2n+1)! (4n+3))
+		let z4 = c_mul(c_mul(z, z), c_mul(z, z));
+		var term = TAU/4.0 * c_mul(z, c_mul(z, z));
+		var sum = term / 3.0;
+		for(var n = 1; n < 8; n++) {
+			let nf = f32(n);
+			term = -c_mul(term, z4) * (TAU * TAU / 16.0) / ((2.0 * nf) * (2.0 * nf + 1.0));
+			sum += term / (4.0 * nf + 3.0);
+		}
+		return sum;
+	}
+	let u = c_erf(0.5 * SQRT_PI * c_mul(vec2(1.0, 1.0), z));
+	let v = c_erf(0.5 * SQRT_PI * c_mul(vec2(1.0, -1.0), z));
+	return 0.25 * c_mul(vec2(1.0, 1.0), u - vec2(-v.y, v.x));
+}
+
+fn c_fresnelc(z: vec2f) -> vec2f {
+	if dot(z, z) < 1.0 {
+		// sum (-1)^n (pi/2)^(2n) z^(4n+1) / ((2n)! (4n+1))
+		let z4 = c_mul(c_mul(z, z), c_mul(z, z));
+		var term = z;
+		var sum = z;
+		for(var n = 1; n < 8; n++) {
+			let nf = f32(n);
+			term = -c_mul(term, z4) * (TAU * TAU / 16.0) / ((2.0 * nf - 1.0) * (2.0 * nf));
+			sum += term / (4.0 * nf + 1.0);
+		}
+		return sum;
+	}
+	let u = c_erf(0.5 * SQRT_PI * c_mul(vec2(1.0, 1.0), z));
+	let v = c_erf(0.5 * SQRT_PI * c_mul(vec2(1.0, -1.0), z));
+	return 0.25 * c_mul(vec2(1.0, -1.0), u + vec2(-v.y, v.x));
+}
+
+// beta //
+
+fn c_beta(a: vec2f, b: vec2f) -> vec2f {
+	return c_exp(c_loggamma(a) + c_loggamma(b) - c_loggamma(a + b));
+}
+
+// incomplete gamma //
+
+// regularized lower incomplete gamma P(a, z)
+fn c_gammainc(a: vec2f, z: vec2f) -> vec2f {
+	if z.x < a.x + 1.0 {
+		return c_gammainc_series(a, z);
+	} else {
+		return C_ONE - c_gammaincc_cf(a, z);
+	}
+}
+
+// regularized upper incomplete gamma Q(a, z)
+fn c_gammaincc(a: vec2f, z: vec2f) -> vec2f {
+	if z.x < a.x + 1.0 {
+		return C_ONE - c_gammainc_series(a, z);
 	} else {
-		return -c_erf_plus(-z);
+		return c_gammaincc_cf(a, z);
+	}
+}
+
+// z^a e^-z / gamma(a)
+fn c_gammainc_prefactor(a: vec2f, z: vec2f) -> vec2f {
+	if z.x == 0.0 && z.y == 0.0 && a.x > 0.0 {
+		return C_ZERO;
+	}
+	return c_exp(c_mul(a, c_log(z)) - z - c_loggamma(a));
+}
+
+fn c_gammainc_series(a: vec2f, z: vec2f) -> vec2f {
+	var ap = a;
+	var term = c_recip(a);
+	var sum = term;
+	for(var k = 0; k < 200; k++) {
+		ap += C_ONE;
+		term = c_div(c_mul(term, z), ap);
+		sum += term;
+		if dot(term, term) < 1.0e-16 * dot(sum, sum) {
+			break;
+		}
+	}
+	return c_mul(sum, c_gammainc_prefactor(a, z));
+}
+
+fn c_gammaincc_cf(a: vec2f, z: vec2f) -> vec2f {
+	return c_mul(c_gammaincc_lentz(a, z), c_gammainc_prefactor(a, z));
+}
+
+// continued fraction for e^z z^-a gamma(a, z), evaluated with
+// the modified Lentz method
+fn c_gammaincc_lentz(a: vec2f, z: vec2f) -> vec2f {
+	var b = z + C_ONE - a;
+	var c = vec2(1.0e30, 0.0);
+	var d = c_recip(b);
+	var h = d;
+	for(var k = 1; k < 200; k++) {
+		let fk = f32(k);
+		let an = -fk * (vec2(fk, 0.0) - a);
+		b += vec2(2.0, 0.0);
+		d = c_mul(an, d) + b;
+		if dot(d, d) < 1.0e-30 {
+			d = vec2(1.0e-15, 0.0);
+		}
+		c = b + c_div(an, c);
+		if dot(c, c) < 1.0e-30 {
+			c = vec2(1.0e-15, 0.0);
+		}
+		d = c_recip(d);
+		let del = c_mul(c, d);
+		h = c_mul(h, del);
+		let err = del - C_ONE;
+		if dot(err, err) < 1.0e-14 {
+			break;
+		}
 	}
+	return h;
 }
 
-const ERF_P = 0.3275911;
-const ERF_A1 = 0.2548295922;
-const ERF_A2 = -0.2844967358;
-const ERF_A3 = 1.4214137412;
-const ERF_A4 = -1.4531520268;
-const ERF_A5 = 1.0614054292;
-fn c_erf_plus(z: vec2f) -> vec2f {
-	let t = c_recip(C_ONE + ERF_P * z);
-	let m = c_exp(-c_mul(z, z));
-	let r = c_mul(t, vec2f(ERF_A1, 0.0)
-		+ c_mul(t, vec2f(ERF_A2, 0.0)
-			+ c_mul(t, vec2f(ERF_A3, 0.0)
-				+ c_mul(t, vec2f(ERF_A4, 0.0) + t * ERF_A5))));
-	return C_ONE - c_mul(m, r);
+// exponential integral //
+
+fn c_e1(z: vec2f) -> vec2f {
+	if c_expint_use_cf(z) {
+		return c_mul(c_gammaincc_lentz(C_ZERO, z), c_exp(-z));
+	}
+	// -emgamma - log(z) - sum (-z)^k / (k k!)
+	var term = -z;
+	var sum = term;
+	for(var k = 2; k < 200; k++) {
+		let fk = f32(k);
+		term = c_mul(term, -z) * ((fk - 1.0) / (fk * fk));
+		sum += term;
+		if dot(term, term) < 1.0e-16 * dot(sum, sum) {
+			break;
+		}
+	}
+	return -C_EMGAMMA - c_log(z) - sum;
+}
+
+fn c_ei(z: vec2f) -> vec2f {
+	if c_expint_use_cf(-z) {
+		return -c_e1(-z) + vec2(0.0, TAU/2.0 * sign(z.y));
+	}
+	// emgamma + log(z) + sum z^k / (k k!)
+	var term = z;
+	var sum = term;
+	for(var k = 2; k < 200; k++) {
+		let fk = f32(k);
+		term = c_mul(term, z) * ((fk - 1.0) / (fk * fk));
+		sum += term;
+		if dot(term, term) < 1.0e-16 * dot(sum, sum) {
+			break;
+		}
+	}
+	// on the negative real axis, take the average of the values on either side
+	var l = c_log(z);
+	if z.y == 0.0 {
+		l.y = 0.0;
+	}
+	return C_EMGAMMA + l + sum;
+}
+
+// the continued fraction for E1 converges slowly near the
+// negative real axis, where the power series is well-behaved
+fn c_expint_use_cf(z: vec2f) -> bool {
+	return dot(z, z) >= 4.0 && (z.x >= 0.0 || abs(z.y) >= 2.0);
+}
+
+fn c_li(z: vec2f) -> vec2f {
+	if z.x == 0.0 && z.y == 0.0 {
+		return C_ZERO;
+	}
+	return c_ei(c_log(z));
+}
+
+// zeta //
+
+// riemann zeta at integers n >= 2
+fn zeta_int(n: i32) -> f32 {
+	if n < 8 {
+		var small = array<f32, 6>(
+			1.6449340668482264, 1.2020569031595942, 1.0823232337111381,
+			1.0369277551433699, 1.0173430619844491, 1.0083492773819228,
+		);
+		return small[n - 2];
+	}
+	var sum = 1.0;
+	for(var k = 2; k < 8; k++) {
+		sum += pow(f32(k), -f32(n));
+	}
+	return sum;
+}
+
+// hurwitz zeta via euler-maclaurin summation, for re(a) >= 0.
+// the number of terms summed directly grows with |s|, but is kept
+// small since the terms cancel badly when re(s) < 0
+fn c_hurwitz_zeta(s: vec2f, a: vec2f) -> vec2f {
+	let n = clamp(i32(ceil(vlength(s))), 2, 16);
+	var sum = C_ZERO;
+	for(var k = 0; k < n; k++) {
+		sum += c_pow(a + vec2(f32(k), 0.0), -s);
+	}
+	let w = a + vec2(f32(n), 0.0);
+	let wr2 = c_recip(c_mul(w, w));
+	let p = c_pow(w, -s);
+	sum += c_div(c_mul(w, p), s - C_ONE) + 0.5 * p;
+
+	// B_2k / (2k)!
+	var coeffs = array<f32, 6>(
+		1.0/12.0, -1.0/720.0, 1.0/30240.0,
+		-1.0/1209600.0, 1.0/47900160.0, -5.2841901e-10,
+	);
+	var t = c_div(c_mul(s, p), w);
+	for(var j = 0; j < 6; j++) {
+		sum += coeffs[j] * t;
+		let fj = f32(2 * j);
+		t = c_mul(t, c_mul(c_mul(s + vec2(fj + 1.0, 0.0), s + vec2(fj + 2.0, 0.0)), wr2));
+	}
+	return sum;
+}
+
+// riemann zeta, using the reflection formula for re(s) < 1/2
+fn c_zeta(s: vec2f) -> vec2f {
+	if s.x >= 0.5 {
+		return c_hurwitz_zeta(s, C_ONE);
+	}
+	let sp = C_ONE - s;
+	let f = c_exp(LOG_2 * s - log(TAU/2.0) * sp + c_loggamma(sp));
+	return c_mul(c_mul(f, c_sin(TAU/4.0 * s)), c_hurwitz_zeta(sp, C_ONE));
+}
+
+// polylogarithm //
+
+fn c_polylog(s: vec2f, z: vec2f) -> vec2f {
+	if dot(z, z) < 0.5625 {
+		return c_polylog_series(s, z);
+	}
+	let n = round(s.x);
+	let eps = s - vec2(n, 0.0);
+	// at z = 1 the series for non-integer s are degenerate
+	if z.x == 1.0 && z.y == 0.0 && s.x > 1.0 && (eps.x != 0.0 || eps.y != 0.0) {
+		return c_zeta(s);
+	}
+	if n == 0.0 && eps.x == 0.0 && eps.y == 0.0 {
+		return c_div(z, C_ONE - z);
+	}
+	if n < 1.0 || dot(eps, eps) >= 0.01 {
+		return c_polylog_nonint(s, z);
+	}
+	// close to a positive integer the expansions for non-integer s
+	// lose precision, so interpolate from a quadratic through s = n
+	// and two points a distance 0.1 away
+	let l0 = c_polylog_int(i32(n), z);
+	if eps.x == 0.0 && eps.y == 0.0 {
+		return l0;
+	}
+	let t = vlength(eps) / 0.1;
+	let d = eps / t;
+	let lp = c_polylog_nonint(vec2(n, 0.0) + d, z);
+	let lm = c_polylog_nonint(vec2(n, 0.0) - d, z);
+	return l0 + 0.5 * t * (lp - lm) + 0.5 * t * t * (lp - 2.0 * l0 + lm);
+}
+
+fn c_polylog_int(n: i32, z: vec2f) -> vec2f {
+	if n == 1 {
+		return -c_log(C_ONE - z);
+	}
+	if dot(z, z) > 1.7777778 {
+		return c_polylog_inversion(n, z);
+	} else {
+		return c_polylog_logseries(n, z);
+	}
+}
+
+fn c_polylog_nonint(s: vec2f, z: vec2f) -> vec2f {
+	let mu = c_log(z);
+	if dot(mu, mu) < 16.0 {
+		return c_polylog_logseries_cx(s, mu);
+	}
+	return c_polylog_jonquiere(s, z);
+}
+
+// sum z^k / k^s, for |z| < 0.75
+fn c_polylog_series(s: vec2f, z: vec2f) -> vec2f {
+	var zk = z;
+	var sum = z;
+	for(var k = 2; k < 60; k++) {
+		zk = c_mul(zk, z);
+		sum += c_mul(zk, c_exp(-s * log(f32(k))));
+	}
+	return sum;
+}
+
+// expansion in powers of log(z) for integer n >= 2, for |log(z)| < tau
+fn c_polylog_logseries(n: i32, z: vec2f) -> vec2f {
+	if z.x == 1.0 && z.y == 0.0 {
+		return vec2(zeta_int(n), 0.0);
+	}
+	let mu = c_log(z);
+	var sum = C_ZERO;
+	// mu^k / k!
+	var t = C_ONE;
+	var harmonic = 0.0;
+	for(var k = 0; k < n - 1; k++) {
+		sum += zeta_int(n - k) * t;
+		t = c_mul(t, mu) / f32(k + 1);
+		harmonic += 1.0 / f32(k + 1);
+	}
+	sum += c_mul(t, vec2(harmonic, 0.0) - c_log(-mu));
+	t = c_mul(t, mu) / f32(n);
+	sum -= 0.5 * t;
+	// zeta(1 - 2j) = (-1)^j 2 (2j-1)! zeta(2j) / tau^2j
+	var c = -2.0 / (TAU * TAU);
+	for(var j = 1; j < 13; j++) {
+		let k = n + 2 * j - 1;
+		t = c_mul(t, mu) / f32(k);
+		sum += c * zeta_int(2 * j) * t;
+		t = c_mul(t, mu) / f32(k + 1);
+		c *= -f32(2 * j * (2 * j + 1)) / (TAU * TAU);
+	}
+	return sum;
+}
+
+// expansion in powers of mu = log(z) for non-integer s, for |mu| < tau
+fn c_polylog_logseries_cx(s: vec2f, mu: vec2f) -> vec2f {
+	var sum = c_exp(c_loggamma(C_ONE - s) + c_mul(s - C_ONE, c_log(-mu)));
+	// mu^k / k!
+	var t = C_ONE;
+	for(var k = 0; k < 32; k++) {
+		sum += c_mul(c_zeta(s - vec2(f32(k), 0.0)), t);
+		t = c_mul(t, mu) / f32(k + 1);
+	}
+	return sum;
+}
+
+// inversion formula for integer n >= 2, for |z| > 1
+fn c_polylog_inversion(n: i32, z: vec2f) -> vec2f {
+	let y = c_log(-z) + vec2(0.0, TAU/2.0);
+	// sum of b_k y^(n-k) / (n-k)!, where b_k = (2 pi i)^k B_k / k!
+	var sum = C_ZERO;
+	var e = C_ONE;
+	for(var m = 0; m <= n; m++) {
+		let k = n - m;
+		if k == 0 {
+			sum += e;
+		} else if k == 1 {
+			sum += c_mul(vec2(0.0, -TAU/2.0), e);
+		} else if k % 2 == 0 {
+			sum -= 2.0 * zeta_int(k) * e;
+		}
+		e = c_mul(e, y) / f32(m + 1);
+	}
+	let sgn = select(1.0, -1.0, n % 2 == 0);
+	return sgn * c_polylog_series(vec2(f32(n), 0.0), c_recip(z)) - sum;
+}
+
+// jonquiere's relation with the hurwitz zeta function
+fn c_polylog_jonquiere(s: vec2f, z: vec2f) -> vec2f {
+	let l = c_log(-z) / TAU;
+	let a1 = vec2(0.5 + l.y, -l.x);
+	let a2 = vec2(0.5 - l.y, l.x);
+	let sigma = C_ONE - s;
+	let ip = c_exp(c_mul(vec2(0.0, TAU/4.0), sigma));
+	let f = c_exp(c_loggamma(sigma) - LOG_TAU * sigma);
+	let h = c_mul(ip, c_hurwitz_zeta(sigma, a1)) + c_mul(c_recip(ip), c_hurwitz_zeta(sigma, a2));
+	return c_mul(f, h);
+}
+
+// mobius transformations and hyperbolic geometry //
+
+fn c_mobius(a: vec2f, b: vec2f, c: vec2f, d: vec2f, z: vec2f) -> vec2f {
+	return c_div(c_mul(a, z) + b, c_mul(c, z) + d);
+}
+
+// upper half-plane to unit disk
+fn c_cayley(z: vec2f) -> vec2f {
+	return c_div(z - C_I, z + C_I);
+}
+
+// unit disk to upper half-plane
+fn c_invcayley(z: vec2f) -> vec2f {
+	return c_div(c_mul(C_I, C_ONE + z), C_ONE - z);
+}
+
+fn c_blaschke(a: vec2f, z: vec2f) -> vec2f {
+	return c_div(z - a, C_ONE - c_mul(c_conj(a), z));
+}
+
+// distance in the poincare disk
+fn c_hdist(z: vec2f, w: vec2f) -> vec2f {
+	let r = vlength(z - w) / vlength(C_ONE - c_mul(c_conj(w), z));
+	return vec2(2.0 * atanh(r), 0.0);
+}
+
+// random //
+
+// pcg3d hash from jarzynski and olano,
+// "hash functions for gpu rendering" (2020)
+fn pcg3d(x: vec3u) -> vec3u {
+	var v = x * 1664525u + 1013904223u;
+	v.x += v.y * v.z;
+	v.y += v.z * v.x;
+	v.z += v.x * v.y;
+	v ^= v >> vec3u(16u);
+	v.x += v.y * v.z;
+	v.y += v.z * v.x;
+	v.z += v.x * v.y;
+	return v;
+}
+
+// hash a point and seed to three uniform values in [0, 1)
+fn hash_uniform(z: vec2f, seed: vec2f) -> vec3f {
+	let s = bitcast<u32>(seed.x) ^ (bitcast<u32>(seed.y) * 0x9e3779b9u);
+	let h = pcg3d(vec3u(bitcast<u32>(z.x), bitcast<u32>(z.y), s));
+	return vec3f(h >> vec3u(8u)) / 16777216.0;
+}
+
+fn c_rand(z: vec2f, seed: vec2f) -> vec2f {
+	return vec2(hash_uniform(z, seed).x, 0.0);
+}
+
+// real and imaginary parts are independent standard normals
+fn c_gaussrand(z: vec2f, seed: vec2f) -> vec2f {
+	let u = hash_uniform(z, seed);
+	let r = sqrt(-2.0 * log(1.0 - u.x));
+	return r * vec2(cos(TAU * u.y), sin(TAU * u.y));
+}
+
+fn noise_grad(corner: vec2f, offset: vec2f) -> f32 {
+	let a = TAU * hash_uniform(corner, C_ZERO).x;
+	return dot(vec2(cos(a), sin(a)), offset);
+}
+
+// perlin gradient noise on the integer lattice, in about [-1, 1]
+fn c_noise(z: vec2f) -> vec2f {
+	let i = floor(z);
+	let f = z - i;
+	let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+	let n00 = noise_grad(i, f);
+	let n10 = noise_grad(i + vec2(1.0, 0.0), f - vec2(1.0, 0.0));
+	let n01 = noise_grad(i + vec2(0.0, 1.0), f - vec2(0.0, 1.0));
+	let n11 = noise_grad(i + vec2(1.0, 1.0), f - vec2(1.0, 1.0));
+	let n = mix(mix(n00, n10, u.x), mix(n01, n11, u.x), u.y);
+	return vec2(RECIP_SQRT2 * 2.0 * n, 0.0);
+}
+
+// real utilities //
+
+fn c_min(u: vec2f, v: vec2f) -> vec2f {
+	return select(u, v, v.x < u.x);
+}
+
+fn c_max(u: vec2f, v: vec2f) -> vec2f {
+	return select(u, v, v.x > u.x);
+}
+
+fn c_clamp(z: vec2f, lo: vec2f, hi: vec2f) -> vec2f {
+	return c_min(c_max(z, lo), hi);
+}
+
+fn c_atan2(y: vec2f, x: vec2f) -> vec2f {
+	return vec2(r_atan2(y.x, x.x), 0.0);
+}
+
+fn c_hypot(u: vec2f, v: vec2f) -> vec2f {
+	return vec2(length(vec4f(u, v)), 0.0);
+}
+
+fn c_sign(z: vec2f) -> vec2f { return sign(z); }
+fn c_frac(z: vec2f) -> vec2f { return fract(z); }
+
+fn c_step(edge: vec2f, z: vec2f) -> vec2f {
+	return vec2(step(edge.x, z.x), 0.0);
+}
+
+fn c_smoothstep(lo: vec2f, hi: vec2f, z: vec2f) -> vec2f {
+	return vec2(r_smoothstep(lo.x, hi.x, z.x), 0.0);
 }
 
 fn c_mix(u: vec2f, v: vec2f, a: vec2f) -> vec2f {
 	return c_mul(u, C_ONE - a) + c_mul(v, a);
 }
 
+//////////////
+//  colors  //
+//////////////
+
+// colors are in the same space as the built in colorings, which main
+// raises to the power 1.68 to get linear light. hues are angles in radians
+
+fn c_rgb(r: f32, g: f32, b: f32) -> vec3f {
+	return clamp(vec3f(r, g, b), vec3f(0.0), vec3f(1.0));
+}
+
+fn c_hsv(h: f32, s: f32, v: f32) -> vec3f {
+	return hsv2rgb(vec3f(h / TAU, saturate(s), saturate(v)));
+}
+
+// lightness from 0 to 1 and chroma from 0 to about 0.4. colors outside
+// the sRGB gamut are clipped
+fn c_oklch(l: f32, c: f32, h: f32) -> vec3f {
+	return oklab2rgb(vec3f(l, c * cos(h), c * sin(h)));
+}
+
+// linear sRGB to the space colors are in, clipped to the gamut
+fn linear2rgb(rgb: vec3f) -> vec3f {
+	return pow(saturate(rgb), vec3f(1.0/1.68));
+}
+
+fn oklab2rgb(lab: vec3f) -> vec3f {
+	let lms_cbrt = vec3f(
+		lab.x + 0.3963377774*lab.y + 0.2158037573*lab.z,
+		lab.x - 0.1055613458*lab.y - 0.0638541728*lab.z,
+		lab.x - 0.0894841775*lab.y - 1.2914855480*lab.z,
+	);
+	let lms = lms_cbrt * lms_cbrt * lms_cbrt;
+	return linear2rgb(vec3f(
+		 4.0767416621*lms.x - 3.3077115913*lms.y + 0.2309699292*lms.z,
+		-1.2684380046*lms.x + 2.6097574011*lms.y - 0.3413193965*lms.z,
+		-0.0041960863*lms.x - 0.7034186147*lms.y + 1.7076147010*lms.z,
+	));
+}
+
+// CIELAB with a D65 white point, lightness from 0 to 100
+fn cielab2rgb(lab: vec3f) -> vec3f {
+	let fy = (lab.x + 16.0) / 116.0;
+	let f = vec3f(fy + lab.y / 500.0, fy, fy - lab.z / 200.0);
+	let delta = 6.0 / 29.0;
+	let linear = select(3.0*delta*delta * (f - 4.0/29.0), f * f * f, f > vec3f(delta));
+	let xyz = linear * vec3f(0.95047, 1.0, 1.08883);
+	return linear2rgb(vec3f(
+		 3.2404542*xyz.x - 1.5371385*xyz.y - 0.4985314*xyz.z,
+		-0.9692660*xyz.x + 1.8760108*xyz.y + 0.0415560*xyz.z,
+		 0.0556434*xyz.x - 0.2040259*xyz.y + 1.0572252*xyz.z,
+	));
+}
+
 /////////////////
 //  rendering  //
 /////////////////
@@ -499,6 +1202,201 @@ fn coloring_uniform(z: vec2f) -> vec3f {
 	return hue * (1.0 - s - v) + s;
 }
 
+// shade a color chosen from the argument toward black near
+// zero and white near infinity, as in coloring_uniform
+fn shade_phase(z: vec2f, hue: vec3f) -> vec3f {
+	if z.x == 0.0 && z.y == 0.0 {
+		return vec3f(0.0, 0.0, 0.0);
+	}
+	if z.x != z.x || z.y != z.y {
+		return vec3f(0.5, 0.5, 0.5);
+	}
+
+	let mag = vlength(z);
+	if mag > 3.40282347E+38 {
+		return vec3f(1.0, 1.0, 1.0);
+	}
+	if uniforms.shading_intensity > 0.0 && mag > 1.8446E+19 {
+		return vec3f(1.0, 1.0, 1.0);
+	}
+	if uniforms.shading_intensity == 0.0 && mag < 1.0E-38 {
+		return vec3f(0.0, 0.0, 0.0);
+	}
+
+	let s = 1.0 - shademap(1.0/mag);
+	let v = 1.0 - shademap(mag);
+	return hue * (1.0 - s - v) + s;
+}
+
+// hues of constant lightness and chroma in OKLCH, turned so that positive reals are red
+fn coloring_oklch(z: vec2f) -> vec3f {
+	let h = c_arg(z).x + 0.5;
+	return shade_phase(z, oklab2rgb(vec3f(0.75, 0.12 * cos(h), 0.12 * sin(h))));
+}
+
+// hues of constant lightness and chroma in CIELAB, turned so that positive reals are red
+fn coloring_cielab(z: vec2f) -> vec3f {
+	let h = c_arg(z).x + 0.7;
+	return shade_phase(z, cielab2rgb(vec3f(70.0, 36.0 * cos(h), 36.0 * sin(h))));
+}
+
+// published cyclic color maps, each sampled in CIELAB at 64 arguments
+// equally spaced counterclockwise from the positive reals. they are
+// rebuilt from the design of each map, its key colors and its range of
+// lightness and chroma, joined in CIELAB and resampled at equal perceptual
+// steps as Kovesi's maps are made, rather than copied from their data
+fn cyclic_map(table: ptr<function, array<vec3f, 64>>, z: vec2f) -> vec3f {
+	let t = emod(c_arg(z).x / TAU, 1.0) * 64.0;
+	let i = u32(t) % 64u;
+	return mix((*table)[i], (*table)[(i + 1u) % 64u], fract(t));
+}
+
+// while shading is on, the lightness of a map is squeezed between these
+// so that its darkest and lightest colors are not lost in the black of
+// zeros and the white of poles
+const MAP_SHADED_LIGHTNESS = vec2f(22.0, 86.0);
+
+// shade a CIELAB color from a map as in shade_phase
+fn shade_map(z: vec2f, lab: vec3f) -> vec3f {
+	var l = lab.x;
+	if uniforms.shading_intensity > 0.0 {
+		l = mix(MAP_SHADED_LIGHTNESS.x, MAP_SHADED_LIGHTNESS.y, lab.x / 100.0);
+	}
+	return shade_phase(z, cielab2rgb(vec3f(l, lab.yz)));
+}
+
+// CET-CBC1, blue, white, yellow and black, which stays distinct with
+// protanopia and deuteranopia as it only uses the blue-yellow axis
+fn coloring_cet_cbc1(z: vec2f) -> vec3f {
+	var table = array<vec3f, 64>(
+		vec3f(56.00, 2.53, -28.91), vec3f(59.06, 2.46, -28.17), vec3f(61.80, 2.33, -26.59), vec3f(64.40, 2.17, -24.78),
+		vec3f(66.97, 2.01, -22.92), vec3f(69.54, 1.84, -21.06), vec3f(72.10, 1.68, -19.20), vec3f(74.67, 1.52, -17.34),
+		vec3f(77.23, 1.35, -15.48), vec3f(79.80, 1.19, -13.62), vec3f(82.36, 1.03, -11.76), vec3f(84.93, 0.87, -9.90),
+		vec3f(87.49, 0.70, -8.04), vec3f(90.05, 0.54, -6.17), vec3f(92.59, 0.38, -4.28), vec3f(95.00, 0.22, -2.22),
+		vec3f(96.65, 0.07, 0.43), vec3f(96.01, 0.01, 3.47), vec3f(94.33, 0.00, 6.16), vec3f(92.54, 0.00, 8.78),
+		vec3f(90.75, 0.00, 11.40), vec3f(88.95, 0.00, 14.01), vec3f(87.16, 0.00, 16.63), vec3f(85.36, 0.00, 19.24),
+		vec3f(83.56, 0.00, 21.86), vec3f(81.76, 0.00, 24.47), vec3f(79.86, 0.00, 27.00), vec3f(77.31, 0.00, 28.83),
+		vec3f(74.18, 0.00, 28.81), vec3f(71.16, 0.00, 27.83), vec3f(68.25, 0.00, 26.56), vec3f(65.38, 0.00, 25.21),
+		vec3f(62.53, 0.00, 23.83), vec3f(59.67, 0.00, 22.45), vec3f(56.82, 0.00, 21.06), vec3f(53.96, 0.00, 19.68),
+		vec3f(51.11, 0.00, 18.29), vec3f(48.25, 0.00, 16.91), vec3f(45.40, 0.00, 15.52), vec3f(42.55, 0.00, 14.14),
+		vec3f(39.69, 0.00, 12.75), vec3f(36.84, 0.00, 11.36), vec3f(33.98, 0.00, 9.98), vec3f(31.13, 0.00, 8.59),
+		vec3f(28.28, 0.00, 7.21), vec3f(25.43, 0.00, 5.81), vec3f(22.59, 0.00, 4.39), vec3f(19.82, 0.01, 2.85),
+		vec3f(17.34, 0.05, 0.89), vec3f(17.20, 0.20, -2.02), vec3f(19.43, 0.38, -4.25), vec3f(21.95, 0.54, -6.17),
+		vec3f(24.51, 0.70, -8.04), vec3f(27.07, 0.87, -9.90), vec3f(29.64, 1.03, -11.76), vec3f(32.20, 1.19, -13.62),
+		vec3f(34.77, 1.35, -15.48), vec3f(37.33, 1.52, -17.34), vec3f(39.90, 1.68, -19.20), vec3f(42.46, 1.84, -21.06),
+		vec3f(45.03, 2.01, -22.92), vec3f(47.60, 2.17, -24.78), vec3f(50.20, 2.33, -26.59), vec3f(52.94, 2.46, -28.17),
+	);
+	return shade_map(z, cyclic_map(&table, z));
+}
+
+// cmocean phase, a circle of hues at constant lightness from gold
+// through magenta, blue and green
+fn coloring_cmocean_phase(z: vec2f) -> vec3f {
+	var table = array<vec3f, 64>(
+		vec3f(53.74, 10.45, 57.53), vec3f(53.74, 15.15, 55.31), vec3f(53.74, 19.84, 53.09), vec3f(53.74, 24.53, 50.88),
+		vec3f(53.74, 29.22, 48.66), vec3f(53.74, 33.91, 46.44), vec3f(53.74, 38.32, 43.87), vec3f(53.74, 41.27, 39.61),
+		vec3f(53.74, 44.22, 35.34), vec3f(53.74, 47.18, 31.08), vec3f(53.74, 50.13, 26.81), vec3f(53.74, 53.08, 22.55),
+		vec3f(53.74, 55.19, 17.97), vec3f(53.74, 55.62, 12.80), vec3f(53.74, 56.04, 7.63), vec3f(53.74, 56.47, 2.46),
+		vec3f(53.74, 56.89, -2.72), vec3f(53.74, 57.32, -7.89), vec3f(53.74, 56.41, -12.82), vec3f(53.74, 54.19, -17.51),
+		vec3f(53.74, 51.98, -22.20), vec3f(53.74, 49.76, -26.89), vec3f(53.74, 47.54, -31.58), vec3f(53.74, 45.33, -36.27),
+		vec3f(53.74, 41.73, -39.80), vec3f(53.74, 37.46, -42.76), vec3f(53.74, 33.20, -45.71), vec3f(53.74, 28.93, -48.66),
+		vec3f(53.74, 24.67, -51.62), vec3f(53.74, 20.40, -54.57), vec3f(53.74, 15.79, -53.07), vec3f(53.74, 11.15, -50.74),
+		vec3f(53.74, 6.52, -48.41), vec3f(53.74, 1.88, -46.08), vec3f(53.74, -2.75, -43.75), vec3f(53.74, -7.39, -41.42),
+		vec3f(53.74, -10.64, -37.39), vec3f(53.74, -13.85, -33.31), vec3f(53.74, -17.06, -29.24), vec3f(53.74, -20.27, -25.16),
+		vec3f(53.74, -23.27, -20.93), vec3f(53.74, -26.22, -16.66), vec3f(53.74, -29.18, -12.40), vec3f(53.74, -31.92, -8.00),
+		vec3f(53.74, -34.53, -3.52), vec3f(53.74, -37.15, 0.96), vec3f(53.74, -39.77, 5.44), vec3f(53.74, -41.28, 10.29),
+		vec3f(53.74, -41.90, 15.44), vec3f(53.74, -42.52, 20.60), vec3f(53.74, -43.14, 25.75), vec3f(53.74, -43.76, 30.90),
+		vec3f(53.74, -44.38, 36.05), vec3f(53.74, -41.76, 39.78), vec3f(53.74, -37.49, 42.74), vec3f(53.74, -33.22, 45.69),
+		vec3f(53.74, -28.96, 48.64), vec3f(53.74, -24.69, 51.60), vec3f(53.74, -20.43, 54.55), vec3f(53.74, -15.40, 55.40),
+		vec3f(53.74, -10.23, 55.83), vec3f(53.74, -5.06, 56.25), vec3f(53.74, 0.11, 56.68), vec3f(53.74, 5.28, 57.10),
+	);
+	return shade_map(z, cyclic_map(&table, z));
+}
+
+// CET-C6, red, yellow, green, cyan, blue and magenta with the
+// primaries and secondaries each matched in lightness
+fn coloring_cet_c6(z: vec2f) -> vec3f {
+	var table = array<vec3f, 64>(
+		vec3f(50.00, 44.15, 39.88), vec3f(52.52, 39.96, 43.66), vec3f(56.16, 35.13, 45.70), vec3f(59.93, 30.27, 47.42),
+		vec3f(63.72, 25.40, 49.06), vec3f(67.52, 20.54, 50.70), vec3f(71.32, 15.68, 52.33), vec3f(75.12, 10.81, 53.96),
+		vec3f(78.92, 5.94, 55.59), vec3f(82.68, 1.05, 57.21), vec3f(86.30, -3.97, 58.78), vec3f(89.30, -9.43, 60.13),
+		vec3f(89.35, -15.59, 60.36), vec3f(85.18, -20.13, 58.91), vec3f(80.15, -23.62, 57.10), vec3f(75.02, -26.94, 55.24),
+		vec3f(69.89, -30.24, 53.38), vec3f(64.75, -33.54, 51.52), vec3f(59.61, -36.83, 49.64), vec3f(54.54, -40.12, 47.58),
+		vec3f(50.27, -43.19, 44.15), vec3f(51.86, -43.26, 38.33), vec3f(55.78, -41.81, 33.51), vec3f(59.96, -40.16, 28.98),
+		vec3f(64.19, -38.47, 24.50), vec3f(68.42, -36.78, 20.03), vec3f(72.66, -35.08, 15.56), vec3f(76.89, -33.39, 11.09),
+		vec3f(81.10, -31.67, 6.61), vec3f(85.18, -29.87, 2.04), vec3f(88.63, -27.68, -2.85), vec3f(89.99, -24.60, -8.19),
+		vec3f(88.36, -20.94, -13.11), vec3f(85.31, -17.30, -17.37), vec3f(81.82, -13.72, -21.34), vec3f(78.21, -10.17, -25.23),
+		vec3f(74.57, -6.62, -29.09), vec3f(70.92, -3.08, -32.96), vec3f(67.28, 0.47, -36.82), vec3f(63.63, 4.02, -40.68),
+		vec3f(59.99, 7.57, -44.54), vec3f(56.36, 11.15, -48.38), vec3f(52.79, 14.85, -52.16), vec3f(50.11, 19.45, -54.92),
+		vec3f(54.35, 19.65, -50.29), vec3f(58.63, 18.78, -45.64), vec3f(62.91, 17.81, -41.00), vec3f(67.19, 16.82, -36.36),
+		vec3f(71.46, 15.83, -31.72), vec3f(75.74, 14.84, -27.08), vec3f(80.00, 13.86, -22.43), vec3f(84.20, 12.94, -17.71),
+		vec3f(88.02, 12.30, -12.65), vec3f(90.00, 12.85, -6.72), vec3f(88.10, 15.31, -1.24), vec3f(84.54, 18.33, 3.10),
+		vec3f(80.62, 21.44, 7.07), vec3f(76.63, 24.57, 10.95), vec3f(72.62, 27.69, 14.81), vec3f(68.61, 30.82, 18.67),
+		vec3f(64.60, 33.95, 22.53), vec3f(60.59, 37.07, 26.40), vec3f(56.60, 40.15, 30.32), vec3f(52.72, 43.02, 34.50),
+	);
+	return shade_map(z, cyclic_map(&table, z));
+}
+
+// CET-C7, yellow, magenta, cyan and green
+fn coloring_cet_c7(z: vec2f) -> vec3f {
+	var table = array<vec3f, 64>(
+		vec3f(90.10, -13.72, 61.50), vec3f(89.18, -10.26, 58.05), vec3f(88.00, -7.24, 54.28), vec3f(86.74, -4.36, 50.41),
+		vec3f(85.46, -1.53, 46.51), vec3f(84.16, 1.27, 42.60), vec3f(82.86, 4.07, 38.69), vec3f(81.56, 6.86, 34.77),
+		vec3f(80.26, 9.66, 30.86), vec3f(78.97, 12.46, 26.95), vec3f(77.67, 15.25, 23.03), vec3f(76.37, 18.05, 19.12),
+		vec3f(75.07, 20.85, 15.21), vec3f(73.77, 23.64, 11.29), vec3f(72.47, 26.44, 7.38), vec3f(71.17, 29.24, 3.47),
+		vec3f(69.87, 32.03, -0.45), vec3f(68.57, 34.83, -4.36), vec3f(67.27, 37.63, -8.28), vec3f(65.97, 40.42, -12.19),
+		vec3f(64.68, 43.21, -16.11), vec3f(63.38, 45.96, -20.05), vec3f(62.10, 48.63, -24.07), vec3f(60.86, 50.94, -28.30),
+		vec3f(60.37, 50.14, -32.48), vec3f(61.51, 45.32, -32.53), vec3f(62.77, 40.57, -31.73), vec3f(64.04, 35.85, -30.77),
+		vec3f(65.32, 31.14, -29.76), vec3f(66.60, 26.43, -28.75), vec3f(67.88, 21.72, -27.74), vec3f(69.17, 17.02, -26.73),
+		vec3f(70.45, 12.31, -25.72), vec3f(71.73, 7.60, -24.71), vec3f(73.01, 2.89, -23.70), vec3f(74.29, -1.81, -22.69),
+		vec3f(75.57, -6.52, -21.68), vec3f(76.85, -11.23, -20.67), vec3f(78.13, -15.94, -19.65), vec3f(79.41, -20.64, -18.64),
+		vec3f(80.69, -25.35, -17.62), vec3f(81.96, -30.05, -16.57), vec3f(83.20, -34.73, -15.38), vec3f(84.32, -39.31, -13.79),
+		vec3f(85.04, -43.34, -11.03), vec3f(84.78, -45.36, -6.55), vec3f(83.96, -45.87, -1.67), vec3f(83.05, -46.12, 3.22),
+		vec3f(82.14, -46.36, 8.12), vec3f(81.23, -46.59, 13.01), vec3f(80.32, -46.83, 17.90), vec3f(79.41, -47.06, 22.79),
+		vec3f(78.51, -47.29, 27.69), vec3f(77.60, -47.53, 32.58), vec3f(76.70, -47.73, 37.48), vec3f(75.97, -47.66, 42.40),
+		vec3f(76.21, -46.03, 47.00), vec3f(77.94, -42.35, 49.82), vec3f(79.93, -38.38, 52.09), vec3f(81.93, -34.40, 54.33),
+		vec3f(83.93, -30.43, 56.57), vec3f(85.92, -26.45, 58.82), vec3f(87.92, -22.47, 61.06), vec3f(89.78, -18.27, 62.92),
+	);
+	return shade_map(z, cyclic_map(&table, z));
+}
+
+// enhanced phase portraits, after Wegert. the argument is colored as in
+// coloring_standard and divided into uniforms.phase_sectors sectors, and
+// the modulus into bands between powers of uniforms.modulus_base. the
+// tiles are squares when the base is exp(TAU / sectors)
+fn phase_tiles(z: vec2f) -> vec2f {
+	let base = max(uniforms.modulus_base, 1.0001);
+	let sectors = f32(max(uniforms.phase_sectors, 1u));
+	let logmag = 0.5 * log(z.x*z.x + z.y*z.y) / log(base);
+	return vec2f(logmag, c_arg(z).x * sectors / TAU);
+}
+
+fn phase_hue(z: vec2f) -> vec3f {
+	return hsv2rgb(vec3f(c_arg(z).x / TAU + 1.0, 1.0, 1.0));
+}
+
+// brightness rising through each band of the modulus, so that
+// it increases toward poles and decreases toward zeros
+fn coloring_modulus_bands(z: vec2f) -> vec3f {
+	let t = phase_tiles(z);
+	return shade_phase(z, phase_hue(z) * (0.6 + 0.4 * fract(t.x)));
+}
+
+// alternate tiles of the modulus bands and argument sectors darkened,
+// so that the checkerboard shows where the function is conformal
+fn coloring_checkerboard(z: vec2f) -> vec3f {
+	let t = floor(phase_tiles(z));
+	let parity = emod(t.x + t.y, 2.0);
+	return shade_phase(z, phase_hue(z) * (0.65 + 0.35 * parity));
+}
+
+// brightness rising through both the modulus bands and argument sectors
+fn coloring_polar_tiles(z: vec2f) -> vec3f {
+	let t = fract(phase_tiles(z));
+	let shade = (0.6 + 0.4 * t.x) * (0.75 + 0.25 * t.y);
+	return shade_phase(z, phase_hue(z) * shade);
+}
+
 fn coloring_none(z: vec2f) -> vec3f {
 	return vec3f(0.5, 0.5, 0.5);
 }
@@ -521,6 +1419,17 @@ fn decoration_contour_mag(z: vec2f) -> f32 {
 	return round(emod(0.5 * logmag, 1.0)) * 2.0 - 1.0;
 }
 
+// alternately -1 and 1 in bands of width spacing, like the built in contours
+fn c_contour(x: f32, spacing: f32) -> f32 {
+	return emod(floor(x / spacing), 2.0) * 2.0 - 1.0;
+}
+
+// 1 where x is level, fading to 0 over about a pixel on either side
+fn c_isoline(x: f32, level: f32) -> f32 {
+	let width = max(fwidth(x), 1.0e-30) * uniforms.res_scale;
+	return 1.0 - saturate(abs(x - level) / width);
+}
+
 fn color_result(z: vec2f) -> vec3f {
 	switch uniforms.coloring {
 		case 0u: {
@@ -532,25 +1441,52 @@ fn color_result(z: vec2f) -> vec3f {
 		case 2u: {
 			return coloring_none(z);
 		}
+		case 3u: {
+			return coloring_oklch(z);
+		}
+		case 4u: {
+			return coloring_cielab(z);
+		}
+		case 5u: {
+			return coloring_cet_cbc1(z);
+		}
+		case 6u: {
+			return coloring_modulus_bands(z);
+		}
+		case 7u: {
+			return coloring_checkerboard(z);
+		}
+		case 8u: {
+			return coloring_polar_tiles(z);
+		}
+		case 9u: {
+			return coloring_cmocean_phase(z);
+		}
+		case 10u: {
+			return coloring_cet_c6(z);
+		}
+		case 11u: {
+			return coloring_cet_c7(z);
+		}
 	}
 }
 
 fn contour_result(z: vec2f) -> f32 {
 	var contours = 1.0;
 
-	if (uniforms.decoration & 0x01u) != 0u {
+	if (uniforms.decorations & 0x01u) != 0u {
 		contours *= decoration_contour_re(z);
 	}
 
-	if (uniforms.decoration & 0x02u) != 0u {
+	if (uniforms.decorations & 0x02u) != 0u {
 		contours *= decoration_contour_im(z);
 	}
 
-	if (uniforms.decoration & 0x04u) != 0u {
+	if (uniforms.decorations & 0x04u) != 0u {
 		contours *= decoration_contour_arg(z);
 	}
 
-	if (uniforms.decoration & 0x08u) != 0u {
+	if (uniforms.decorations & 0x08u) != 0u {
 		contours *= decoration_contour_mag(z);
 	}
 
@@ -595,13 +1531,23 @@ fn grid_axes(pos: vec2f) -> f32 {
 @fragment
 fn main(@builtin(position) in: vec4f) -> @location(0) vec4f {
 	let pos = vec2(in.x, f32(uniforms.resolution.y) - in.y);
-	let z = screen2cx(pos);
+	let z = mobius_view(screen2cx(pos));
 
+	iterations_left = uniforms.iteration_budget;
 	let w = func_plot(z);
 
-	let col = color_result(w);
+	let col = plot_color(w);
 	let contours = contour_result(w);
-	let plot_col = mix(col, vec3f(contours * 0.5 + 0.5), uniforms.contour_intensity);
+	var plot_col = mix(col, vec3f(contours * 0.5 + 0.5), uniforms.contour_intensity);
+	// a program's decor function shades toward white where it is
+	// positive and toward black where it is negative
+	let decor = plot_decor(w);
+	if decor == decor {
+		plot_col = mix(plot_col, vec3f(step(0.0, decor)), min(abs(decor), 1.0));
+	}
+	if budget_exceeded {
+		plot_col = BUDGET_EXCEEDED_COLOR;
+	}
 
 	var grid_val = 0.0;
 	switch (uniforms.grid_mode) {
@@ -1,54 +1,76 @@
-use std::{num::NonZeroU64, io::Cursor};
-
 use wgpu::util::DeviceExt;
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct Uniforms {
-	pub variables: [f32; 16],
-	pub resolution: (u32, u32),
-	pub bounds_min: (f32, f32),
-	pub bounds_max: (f32, f32),
-    pub res_scale: f32,
-	pub shading_intensity: f32,
-	pub contour_intensity: f32,
-	pub decorations: u32,
-	pub coloring: u32,
-    pub grid_mode: u32,
-}
+use crate::language::{HostConstants, MAX_VARIABLES};
 
-const UNIFORM_SIZE: usize = std::mem::size_of::<Uniforms>();
+use self::uniforms::{uniform_struct, UniformLayout};
 
-impl Uniforms {
-	pub fn encode(&self, buf: &mut impl std::io::Write) -> Result<(), std::io::Error> {
-		for var in self.variables {
-			buf.write_all(&var.to_le_bytes())?;
-		}
-		buf.write_all(&self.resolution.0.to_le_bytes())?;
-		buf.write_all(&self.resolution.1.to_le_bytes())?;
-		buf.write_all(&self.bounds_min.0.to_le_bytes())?;
-		buf.write_all(&self.bounds_min.1.to_le_bytes())?;
-		buf.write_all(&self.bounds_max.0.to_le_bytes())?;
-		buf.write_all(&self.bounds_max.1.to_le_bytes())?;
-		buf.write_all(&self.res_scale.to_le_bytes())?;
-		buf.write_all(&self.shading_intensity.to_le_bytes())?;
-		buf.write_all(&self.contour_intensity.to_le_bytes())?;
-		buf.write_all(&self.decorations.to_le_bytes())?;
-		buf.write_all(&self.coloring.to_le_bytes())?;
-		buf.write_all(&self.grid_mode.to_le_bytes())?;
-		Ok(())
+pub mod uniforms;
+
+pub use uniforms::{CustomUniform, CustomValue};
+
+uniform_struct! {
+	/// The built in uniforms. The WGSL struct and the encoding of the buffer
+	/// are both generated from these fields, see `UniformLayout`
+	#[derive(Debug)]
+	pub struct Uniforms {
+		pub resolution: (u32, u32),
+		pub bounds_min: (f32, f32),
+		pub bounds_max: (f32, f32),
+		pub res_scale: f32,
+		pub shading_intensity: f32,
+		pub contour_intensity: f32,
+		pub decorations: u32,
+		pub coloring: u32,
+		pub grid_mode: u32,
+		pub mobius: [f32; 8],
+		/// Total number of loop iterations a compiled program
+		/// may run for each pixel before it is abandoned
+		pub iteration_budget: u32,
+		/// Seconds since the animation started, read as `t` by programs
+		pub time: f32,
+		/// Number of frames drawn since the animation started, read as `frame`
+		pub frame: u32,
+		/// The modulus bands of the enhanced phase portraits
+		/// are between powers of this
+		pub modulus_base: f32,
+		/// Number of argument sectors in the enhanced phase portraits
+		pub phase_sectors: u32,
 	}
 }
 
+const VARIABLES_SIZE: usize = 2*MAX_VARIABLES*std::mem::size_of::<f32>();
+
+pub const DEFAULT_ITERATION_BUDGET: u32 = 65536;
+pub const DEFAULT_MODULUS_BASE: f32 = 2.0;
+pub const DEFAULT_PHASE_SECTORS: u32 = 16;
+
+/// The fragment shader that compiled programs are appended to,
+/// without the declaration of the uniform struct
+const FRAGMENT_SRC: &str = include_str!("fragment.wgsl");
+
+/// The fragment shader that compiled programs are appended to, with the
+/// uniform struct including the given custom uniforms and host constants
+pub fn fragment_src(custom: &[CustomUniform], host_constants: usize) -> String {
+	UniformLayout::new(custom, host_constants).wgsl() + FRAGMENT_SRC
+}
+
 pub struct WgpuState<'a> {
 	pub uniforms: Uniforms,
-	surface: wgpu::Surface<'a>,
+	/// The user variables, packed as the real and imaginary parts of each
+	pub variables: Vec<f32>,
+	host_constants: HostConstants,
+	// the values of the host constants, packed as the real and imaginary parts of each
+	constants: Vec<f32>,
+	custom_uniforms: Vec<CustomUniform>,
+	uniform_fields: UniformLayout,
+	surface: Option<wgpu::Surface<'a>>,
 	device: wgpu::Device,
 	config: wgpu::SurfaceConfiguration,
 	render_pipeline: Option<wgpu::RenderPipeline>,
 	uniform_bind_group: wgpu::BindGroup,
 	uniform_layout: wgpu::BindGroupLayout,
 	uniform_buffer: wgpu::Buffer,
+	variable_buffer: wgpu::Buffer,
 	queue: wgpu::Queue
 }
 
@@ -58,10 +80,23 @@ impl<'a> WgpuState<'a> {
 
 		let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
 		let surface = instance.create_surface(window).unwrap();
+		Self::with_surface(instance, Some(surface), size).await
+	}
 
+	/// Create a renderer without a window, which can only draw with `render_image`.
+	/// The backends to try can be chosen with the `WGPU_BACKEND` environment variable
+	pub async fn new_headless(size: (u32, u32)) -> Self {
+		let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+			backends: wgpu::util::backend_bits_from_env().unwrap_or_default(),
+			..Default::default()
+		});
+		Self::with_surface(instance, None, size).await
+	}
+
+	async fn with_surface(instance: wgpu::Instance, surface: Option<wgpu::Surface<'a>>, size: (u32, u32)) -> Self {
 		let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
 			power_preference: wgpu::PowerPreference::default(),
-			compatible_surface: Some(&surface),
+			compatible_surface: surface.as_ref(),
 			force_fallback_adapter: false,
 		}).await.unwrap();
 
@@ -78,7 +113,10 @@ impl<'a> WgpuState<'a> {
 			None
 		).await.map_err(|e| e.to_string()).unwrap();
         
-		let format = surface.get_capabilities(&adapter).formats[0];
+		let format = match &surface {
+			Some(surface) => surface.get_capabilities(&adapter).formats[0],
+			None => wgpu::TextureFormat::Rgba8UnormSrgb,
+		};
 
 		let config = wgpu::SurfaceConfiguration {
 			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -90,14 +128,21 @@ impl<'a> WgpuState<'a> {
 			view_formats: vec![format],
             desired_maximum_frame_latency: 2,
 		};
-		surface.configure(&device, &config);
+		if let Some(surface) = &surface {
+			surface.configure(&device, &config);
+		}
 
 		//  Uniforms  //
 
-		let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		let uniform_fields = UniformLayout::new(&[], 0);
+		let uniform_buffer = create_uniform_buffer(&device, uniform_fields.size());
+
+		// large enough for the most variables a program can use,
+		// so that it does not need to be recreated for each program
+		let variable_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: None,
 			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-			contents: &[0; UNIFORM_SIZE],
+			contents: &[0; VARIABLES_SIZE],
 		});
 
 		let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -113,28 +158,24 @@ impl<'a> WgpuState<'a> {
 					},
 					count: None,
 				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
 			]
 		});
 
-		let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-			label: None,
-			layout: &uniform_layout,
-			entries: &[
-				wgpu::BindGroupEntry {
-					binding: 1,
-					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-						buffer: &uniform_buffer,
-						offset: 0,
-						size: Some(NonZeroU64::new(UNIFORM_SIZE as u64).unwrap()),
-					}),
-				},
-			],
-		});
+		let uniform_bind_group = create_bind_group(&device, &uniform_layout, &uniform_buffer, &variable_buffer);
 
 		//  Done  //
 
 		let uniforms = Uniforms {
-			variables: [0.0; 16],
 			resolution: size.into(),
 			bounds_min: (-0.0, -0.0),
 			bounds_max: ( 0.0,  0.0),
@@ -144,10 +185,21 @@ impl<'a> WgpuState<'a> {
 			decorations: 0,
 			coloring: 0,
             grid_mode: 0,
+			mobius: [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+			iteration_budget: DEFAULT_ITERATION_BUDGET,
+			time: 0.0,
+			frame: 0,
+			modulus_base: DEFAULT_MODULUS_BASE,
+			phase_sectors: DEFAULT_PHASE_SECTORS,
 		};
 
 		Self {
 			uniforms,
+			variables: vec![0.0; 2*MAX_VARIABLES],
+			host_constants: HostConstants::default(),
+			constants: Vec::new(),
+			custom_uniforms: Vec::new(),
+			uniform_fields,
 			surface,
 			config,
 			device,
@@ -155,14 +207,60 @@ impl<'a> WgpuState<'a> {
 			uniform_bind_group,
 			uniform_layout,
 			uniform_buffer,
+			variable_buffer,
 			queue,
 		}
 	}
 
-	pub fn load_shaders(&mut self, userdefs: &str) {
+	/// Set the time and frame number that programs read as `t` and `frame`
+	pub fn set_time(&mut self, time: f32, frame: u32) {
+		self.uniforms.time = time;
+		self.uniforms.frame = frame;
+	}
+
+	/// The custom uniforms, which programs should be compiled with
+	pub fn custom_uniforms(&self) -> &[CustomUniform] {
+		&self.custom_uniforms
+	}
+
+	/// Add a custom uniform, or replace the one with the same name. It
+	/// becomes part of the uniform struct when shaders are next loaded
+	pub fn add_custom_uniform(&mut self, name: &str, value: CustomValue) {
+		match self.custom_uniforms.iter_mut().find(|u| u.name == name) {
+			Some(u) => u.value = value,
+			None => self.custom_uniforms.push(CustomUniform { name: name.to_owned(), value }),
+		}
+	}
+
+	/// Set the value of a custom uniform. Returns false if there is
+	/// none with the name, or if it was added with a different type
+	pub fn set_custom_uniform(&mut self, name: &str, value: CustomValue) -> bool {
+		let Some(u) = self.custom_uniforms.iter_mut().find(|u| u.name == name) else {
+			return false
+		};
+		if std::mem::discriminant(&u.value) != std::mem::discriminant(&value) {
+			return false
+		}
+		u.value = value;
+		true
+	}
+
+	/// Load a compiled program, along with the constants
+	/// it was compiled to read from the uniforms
+	pub fn load_shaders(&mut self, userdefs: &str, host_constants: HostConstants) {
+		//  Uniforms  //
+		self.constants = vec![0.0; 2*host_constants.0.len()];
+		self.host_constants = host_constants;
+		let uniform_fields = UniformLayout::new(&self.custom_uniforms, self.host_constants.0.len());
+		if uniform_fields.size() != self.uniform_fields.size() {
+			self.uniform_buffer = create_uniform_buffer(&self.device, uniform_fields.size());
+			self.uniform_bind_group = create_bind_group(&self.device, &self.uniform_layout, &self.uniform_buffer, &self.variable_buffer);
+		}
+		let fragment_src = uniform_fields.wgsl() + FRAGMENT_SRC + userdefs;
+		self.uniform_fields = uniform_fields;
+
 		//  Shaders  //
 		let vertex_src = include_str!("vertex.wgsl").to_owned();
-		let fragment_src = include_str!("fragment.wgsl").to_owned() + userdefs;
 
 		let vertex_module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
 			label: None,
@@ -218,13 +316,14 @@ impl<'a> WgpuState<'a> {
 		self.render_pipeline = Some(render_pipeline);
 	}
 
-	pub fn redraw(&self) {
-		let frame = self.surface.get_current_texture().unwrap();
-		let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+	// record drawing to view, writing the uniforms for it
+	fn encode_draw(&mut self, view: &wgpu::TextureView) -> wgpu::CommandEncoder {
+		self.host_constants.eval(&self.variables, &mut self.constants);
+
 		let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 		{
 			let color_attachment = wgpu::RenderPassColorAttachment {
-				view: &view,
+				view,
 				resolve_target: None,
 				ops: wgpu::Operations {
 					load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
@@ -245,18 +344,125 @@ impl<'a> WgpuState<'a> {
 				rpass.draw(0..3, 0..1);
 			}
 		}
-		let mut cursor = Cursor::new([0; UNIFORM_SIZE]);
-		self.uniforms.encode(&mut cursor).unwrap();
-		self.queue.write_buffer(&self.uniform_buffer, 0, &cursor.into_inner());
+		let uniforms = self.uniform_fields.encode(&self.uniforms, &self.custom_uniforms, &self.constants);
+		self.queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+		let variables: Vec<u8> = self.variables.iter().flat_map(|v| v.to_le_bytes()).collect();
+		self.queue.write_buffer(&self.variable_buffer, 0, &variables);
+		encoder
+	}
+
+	/// Draw to the window. Does nothing for a headless renderer
+	pub fn redraw(&mut self) {
+		let Some(surface) = &self.surface else {
+			return
+		};
+		let frame = surface.get_current_texture().unwrap();
+		let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let encoder = self.encode_draw(&view);
 		self.queue.submit(Some(encoder.finish()));
 		frame.present();
 	}
 
+	/// Draw to an offscreen texture the size of the view and return its pixels, as
+	/// rows of sRGB RGBA bytes from the top. This blocks until the GPU is done, so
+	/// it is not for the web. The surface format must have 8-bit RGBA or BGRA
+	/// channels, which it always does for a headless renderer
+	pub fn render_image(&mut self) -> Vec<u8> {
+		let format = self.config.format;
+		let bgra = match format {
+			wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+			wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+			_ => panic!("cannot read back a texture with format {format:?}"),
+		};
+		let size = wgpu::Extent3d {
+			width: self.config.width,
+			height: self.config.height,
+			depth_or_array_layers: 1,
+		};
+		let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+			label: None,
+			size,
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+			view_formats: &[],
+		});
+		let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let mut encoder = self.encode_draw(&view);
+
+		// rows of the copy must be aligned, so they are trimmed afterwards
+		let row_len = 4*size.width;
+		let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+		let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+			label: None,
+			size: padded_row_len as u64 * size.height as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		encoder.copy_texture_to_buffer(
+			texture.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(padded_row_len),
+					rows_per_image: None,
+				},
+			},
+			size,
+		);
+		self.queue.submit(Some(encoder.finish()));
+
+		let slice = buffer.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |result| result.expect("failed to read back the image"));
+		self.device.poll(wgpu::Maintain::Wait);
+		let mut pixels = Vec::with_capacity((row_len * size.height) as usize);
+		for row in slice.get_mapped_range().chunks(padded_row_len as usize) {
+			pixels.extend_from_slice(&row[..row_len as usize]);
+		}
+		if bgra {
+			for pixel in pixels.chunks_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+		pixels
+	}
+
 	pub fn resize(&mut self, size: (u32, u32)) {
 		let size = (size.0.max(1).min(8192), size.1.max(1).min(8192));
 		self.config.width = size.0;
 		self.config.height = size.1;
-		self.surface.configure(&self.device, &self.config);
+		if let Some(surface) = &self.surface {
+			surface.configure(&self.device, &self.config);
+		}
 		self.uniforms.resolution = size.into();
 	}
 }
+
+fn create_uniform_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+	device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: None,
+		usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+		contents: &vec![0; size],
+	})
+}
+
+fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer,
+variable_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+	device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label: None,
+		layout,
+		entries: &[
+			wgpu::BindGroupEntry {
+				binding: 1,
+				resource: uniform_buffer.as_entire_binding(),
+			},
+			wgpu::BindGroupEntry {
+				binding: 2,
+				resource: variable_buffer.as_entire_binding(),
+			},
+		],
+	})
+}
@@ -0,0 +1,235 @@
+use std::fmt::Write;
+
+use crate::language::format_custom_uniform;
+
+/// Types that uniform fields may have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniformType {
+	F32,
+	U32,
+	Vec2f,
+	Vec2u,
+	/// An array of this many vec4f
+	Vec4fArray(usize),
+}
+
+impl UniformType {
+	pub fn wgsl(self) -> String {
+		match self {
+			UniformType::F32 => "f32".to_owned(),
+			UniformType::U32 => "u32".to_owned(),
+			UniformType::Vec2f => "vec2f".to_owned(),
+			UniformType::Vec2u => "vec2u".to_owned(),
+			UniformType::Vec4fArray(n) => format!("array<vec4f, {n}>"),
+		}
+	}
+
+	// alignment and size in the uniform address space
+	fn align(self) -> usize {
+		match self {
+			UniformType::F32 | UniformType::U32 => 4,
+			UniformType::Vec2f | UniformType::Vec2u => 8,
+			UniformType::Vec4fArray(_) => 16,
+		}
+	}
+
+	fn size(self) -> usize {
+		match self {
+			UniformType::F32 | UniformType::U32 => 4,
+			UniformType::Vec2f | UniformType::Vec2u => 8,
+			UniformType::Vec4fArray(n) => 16*n,
+		}
+	}
+}
+
+/// A Rust type that can be the type of a field of `Uniforms`
+pub trait UniformField {
+	const TYPE: UniformType;
+
+	/// Write the value to the start of buf, which is at least `TYPE`'s size
+	fn write(&self, buf: &mut [u8]);
+}
+
+impl UniformField for f32 {
+	const TYPE: UniformType = UniformType::F32;
+
+	fn write(&self, buf: &mut [u8]) {
+		buf[..4].copy_from_slice(&self.to_le_bytes());
+	}
+}
+
+impl UniformField for u32 {
+	const TYPE: UniformType = UniformType::U32;
+
+	fn write(&self, buf: &mut [u8]) {
+		buf[..4].copy_from_slice(&self.to_le_bytes());
+	}
+}
+
+impl UniformField for (f32, f32) {
+	const TYPE: UniformType = UniformType::Vec2f;
+
+	fn write(&self, buf: &mut [u8]) {
+		self.0.write(&mut buf[..4]);
+		self.1.write(&mut buf[4..]);
+	}
+}
+
+impl UniformField for (u32, u32) {
+	const TYPE: UniformType = UniformType::Vec2u;
+
+	fn write(&self, buf: &mut [u8]) {
+		self.0.write(&mut buf[..4]);
+		self.1.write(&mut buf[4..]);
+	}
+}
+
+// packed into vec4fs, so N must be a multiple of 4. this is checked
+// when the type is used, since otherwise the array would be declared
+// shorter than it is written
+impl<const N: usize> UniformField for [f32; N] {
+	const TYPE: UniformType = {
+		assert!(N.is_multiple_of(4), "uniform arrays of f32 must have a multiple of 4 elements");
+		UniformType::Vec4fArray(N / 4)
+	};
+
+	fn write(&self, buf: &mut [u8]) {
+		for (i, x) in self.iter().enumerate() {
+			x.write(&mut buf[4*i..]);
+		}
+	}
+}
+
+/// Declare the uniform struct. Along with the struct this generates
+/// `FIELDS`, the name and type of each field in order, and `write_fields`,
+/// which writes each field at the corresponding offset
+macro_rules! uniform_struct {
+	(
+		$(#[$meta:meta])*
+		pub struct $name:ident {
+			$( $(#[$field_meta:meta])* pub $field:ident: $ty:ty, )*
+		}
+	) => {
+		$(#[$meta])*
+		pub struct $name {
+			$( $(#[$field_meta])* pub $field: $ty, )*
+		}
+
+		impl $name {
+			pub const FIELDS: &'static [(&'static str, $crate::renderer::uniforms::UniformType)] = &[
+				$( (stringify!($field), <$ty as $crate::renderer::uniforms::UniformField>::TYPE), )*
+			];
+
+			fn write_fields(&self, offsets: &[usize], buf: &mut [u8]) {
+				let mut offsets = offsets.iter();
+				$(
+					let offset = *offsets.next().expect("too few offsets");
+					$crate::renderer::uniforms::UniformField::write(&self.$field, &mut buf[offset..]);
+				)*
+			}
+		}
+	};
+}
+
+pub(crate) use uniform_struct;
+
+/// A uniform field added by the embedder, which programs can read by name
+#[derive(Clone, Debug)]
+pub struct CustomUniform {
+	pub name: String,
+	pub value: CustomValue,
+}
+
+/// The value of a custom uniform, whose type stays the same once it is added
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CustomValue {
+	Real(f32),
+	Complex(f32, f32),
+}
+
+impl CustomUniform {
+	fn ty(&self) -> UniformType {
+		match self.value {
+			CustomValue::Real(_) => UniformType::F32,
+			CustomValue::Complex(..) => UniformType::Vec2f,
+		}
+	}
+
+	fn write(&self, buf: &mut [u8]) {
+		match self.value {
+			CustomValue::Real(x) => x.write(buf),
+			CustomValue::Complex(re, im) => (re, im).write(buf),
+		}
+	}
+}
+
+/// The fields of the uniform struct, those of `Uniforms` followed by any
+/// custom ones, then the host constants of the program if it has any,
+/// at the offsets WGSL gives them in the uniform address space
+#[derive(Clone, Debug)]
+pub struct UniformLayout {
+	fields: Vec<(String, UniformType)>,
+	offsets: Vec<usize>,
+	size: usize,
+	// the offset of the host constants, if there are any
+	constants: Option<usize>,
+}
+
+impl UniformLayout {
+	pub fn new(custom: &[CustomUniform], host_constants: usize) -> Self {
+		let mut fields: Vec<(String, UniformType)> = super::Uniforms::FIELDS.iter()
+			.map(|(name, ty)| (name.to_string(), *ty))
+			.chain(custom.iter().map(|u| (format_custom_uniform(&u.name), u.ty())))
+			.collect();
+		// two constants are packed into each vec4f
+		if host_constants > 0 {
+			fields.push(("constants".to_owned(), UniformType::Vec4fArray(host_constants.div_ceil(2))));
+		}
+		let mut offsets = Vec::with_capacity(fields.len());
+		let mut offset = 0usize;
+		for (_, ty) in &fields {
+			offset = offset.next_multiple_of(ty.align());
+			offsets.push(offset);
+			offset += ty.size();
+		}
+		// the struct's alignment is at most 16, and rounding up to
+		// a multiple of 16 is also what is needed to put it in an array
+		let constants = (host_constants > 0).then(|| offsets[offsets.len() - 1]);
+		Self { fields, offsets, size: offset.next_multiple_of(16), constants }
+	}
+
+	/// Size in bytes of the uniform buffer
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// The WGSL declaration of the struct
+	pub fn wgsl(&self) -> String {
+		let mut result = "struct Uniforms {\n".to_owned();
+		for (name, ty) in &self.fields {
+			writeln!(result, "\t{name}: {},", ty.wgsl()).expect("writing to a string cannot fail");
+		}
+		result.push_str("}\n");
+		result
+	}
+
+	pub fn encode(&self, uniforms: &super::Uniforms, custom: &[CustomUniform], constants: &[f32]) -> Vec<u8> {
+		let mut buf = vec![0; self.size];
+		let builtin = super::Uniforms::FIELDS.len();
+		uniforms.write_fields(&self.offsets[..builtin], &mut buf);
+		// custom uniforms added, or given another type, since the
+		// layout was made are left out
+		let slots = self.fields[builtin..].iter().zip(&self.offsets[builtin..]);
+		for (u, ((_, ty), offset)) in custom.iter().zip(slots) {
+			if u.ty() == *ty {
+				u.write(&mut buf[*offset..]);
+			}
+		}
+		if let Some(offset) = self.constants {
+			for (i, x) in constants.iter().enumerate() {
+				x.write(&mut buf[offset + 4*i..]);
+			}
+		}
+		buf
+	}
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{link::LINK_VERSION, renderer::{Uniforms, DEFAULT_ITERATION_BUDGET, DEFAULT_MODULUS_BASE, DEFAULT_PHASE_SECTORS}, timeline::Timeline};
+
+/// The version of the session format written by `Session::save`
+pub const SESSION_VERSION: u32 = 1;
+
+type Migration = fn(&mut Value) -> Result<(), SessionError>;
+
+// upgrade a session from the version at each index plus one to
+// the next version. changing the format means increasing
+// SESSION_VERSION and adding a migration here
+const MIGRATIONS: &[Migration] = &[];
+
+/// Everything needed to reproduce a graph
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+	pub version: u32,
+	/// The program's source text
+	pub source: String,
+	pub view: View,
+	#[serde(default)]
+	pub settings: Settings,
+	#[serde(default)]
+	pub variables: Vec<Variable>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub timeline: Option<Timeline>,
+}
+
+/// The region of the plane that is shown
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+	pub bounds_min: [f64; 2],
+	pub bounds_max: [f64; 2],
+	/// The coefficients `[a, b, c, d]` of the Möbius transformation the view is mapped
+	/// through, as the real and imaginary parts of each
+	#[serde(default = "identity_mobius")]
+	pub mobius: [f64; 8],
+}
+
+impl View {
+	/// The coefficients of the transformation that leaves the view as it is
+	pub const IDENTITY_MOBIUS: [f64; 8] = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+}
+
+fn identity_mobius() -> [f64; 8] {
+	View::IDENTITY_MOBIUS
+}
+
+/// Settings for how the plot is drawn, as in `Uniforms`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	pub res_scale: f64,
+	pub shading_intensity: f64,
+	pub contour_intensity: f64,
+	pub decorations: u32,
+	pub coloring: u32,
+	pub grid_mode: u32,
+	pub iteration_budget: u32,
+	pub modulus_base: f64,
+	pub phase_sectors: u32,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			res_scale: 1.0,
+			shading_intensity: 0.3,
+			contour_intensity: 0.0,
+			decorations: 0,
+			coloring: 0,
+			grid_mode: 0,
+			iteration_budget: DEFAULT_ITERATION_BUDGET,
+			modulus_base: DEFAULT_MODULUS_BASE as f64,
+			phase_sectors: DEFAULT_PHASE_SECTORS,
+		}
+	}
+}
+
+/// A user variable and how it is controlled
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Variable {
+	Slider { name: String, value: f64, min: f64, max: f64, step: f64 },
+	Point { name: String, value: [f64; 2] },
+}
+
+impl Variable {
+	pub fn name(&self) -> &str {
+		match self {
+			Variable::Slider { name, .. } | Variable::Point { name, .. } => name,
+		}
+	}
+
+	/// The value as its real and imaginary parts
+	pub fn value(&self) -> [f64; 2] {
+		match self {
+			Variable::Slider { value, .. } => [*value, 0.0],
+			Variable::Point { value, .. } => *value,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionFormat {
+	Json,
+	Toml,
+}
+
+impl SessionFormat {
+	/// The format for a file extension, if it is one
+	pub fn from_extension(ext: &str) -> Option<Self> {
+		match ext.to_ascii_lowercase().as_str() {
+			"json" => Some(SessionFormat::Json),
+			"toml" => Some(SessionFormat::Toml),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionError {
+	/// The text is not valid in the format, or does not describe a session
+	Invalid(String),
+	/// The session was saved with a newer version of the format than this one
+	NewerVersion(u32),
+	/// The link was made with a newer version of the link format than this one
+	NewerLinkVersion(u8),
+}
+
+impl fmt::Display for SessionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SessionError::Invalid(msg) => write!(f, "invalid session: {msg}"),
+			SessionError::NewerVersion(version) => write!(f,
+				"session has version {version}, but only versions up to {SESSION_VERSION} are supported"),
+			SessionError::NewerLinkVersion(version) => write!(f,
+				"link has version {version}, but only versions up to {LINK_VERSION} are supported"),
+		}
+	}
+}
+
+impl std::error::Error for SessionError {}
+
+impl Session {
+	/// A session for a program with the given view and default settings
+	pub fn new(source: String, bounds_min: [f64; 2], bounds_max: [f64; 2]) -> Self {
+		Self {
+			version: SESSION_VERSION,
+			source,
+			view: View { bounds_min, bounds_max, mobius: identity_mobius() },
+			settings: Settings::default(),
+			variables: Vec::new(),
+			timeline: None,
+		}
+	}
+
+	/// Read a session, upgrading it if it has an older version
+	pub fn load(text: &str, format: SessionFormat) -> Result<Self, SessionError> {
+		let value: Value = match format {
+			SessionFormat::Json => serde_json::from_str(text).map_err(|e| SessionError::Invalid(e.to_string()))?,
+			SessionFormat::Toml => toml::from_str(text).map_err(|e| SessionError::Invalid(e.to_string()))?,
+		};
+		Self::from_value(value)
+	}
+
+	/// Upgrade a session in its generic form and read it
+	pub fn from_value(mut value: Value) -> Result<Self, SessionError> {
+		let version = value.get("version")
+			.and_then(Value::as_u64)
+			.ok_or_else(|| SessionError::Invalid("missing version".to_owned()))?;
+		let version = u32::try_from(version).map_err(|_| SessionError::NewerVersion(u32::MAX))?;
+		if version > SESSION_VERSION {
+			return Err(SessionError::NewerVersion(version))
+		}
+		if version == 0 {
+			return Err(SessionError::Invalid("version 0 does not exist".to_owned()))
+		}
+		for migration in &MIGRATIONS[version as usize - 1..] {
+			migration(&mut value)?;
+		}
+		value["version"] = SESSION_VERSION.into();
+		serde_json::from_value(value).map_err(|e| SessionError::Invalid(e.to_string()))
+	}
+
+	/// Write the session, always with the current version
+	pub fn save(&self, format: SessionFormat) -> Result<String, SessionError> {
+		let session = Self { version: SESSION_VERSION, ..self.clone() };
+		match format {
+			SessionFormat::Json => serde_json::to_string_pretty(&session).map_err(|e| SessionError::Invalid(e.to_string())),
+			SessionFormat::Toml => toml::to_string_pretty(&session).map_err(|e| SessionError::Invalid(e.to_string())),
+		}
+	}
+
+	/// Take the view and settings from the uniforms
+	pub fn read_uniforms(&mut self, uniforms: &Uniforms) {
+		self.view.bounds_min = [uniforms.bounds_min.0 as f64, uniforms.bounds_min.1 as f64];
+		self.view.bounds_max = [uniforms.bounds_max.0 as f64, uniforms.bounds_max.1 as f64];
+		self.view.mobius = uniforms.mobius.map(f64::from);
+		self.settings = Settings {
+			res_scale: uniforms.res_scale as f64,
+			shading_intensity: uniforms.shading_intensity as f64,
+			contour_intensity: uniforms.contour_intensity as f64,
+			decorations: uniforms.decorations,
+			coloring: uniforms.coloring,
+			grid_mode: uniforms.grid_mode,
+			iteration_budget: uniforms.iteration_budget,
+			modulus_base: uniforms.modulus_base as f64,
+			phase_sectors: uniforms.phase_sectors,
+		};
+	}
+
+	/// Set the view and settings in the uniforms
+	pub fn write_uniforms(&self, uniforms: &mut Uniforms) {
+		uniforms.bounds_min = (self.view.bounds_min[0] as f32, self.view.bounds_min[1] as f32);
+		uniforms.bounds_max = (self.view.bounds_max[0] as f32, self.view.bounds_max[1] as f32);
+		uniforms.mobius = self.view.mobius.map(|x| x as f32);
+		uniforms.res_scale = self.settings.res_scale as f32;
+		uniforms.shading_intensity = self.settings.shading_intensity as f32;
+		uniforms.contour_intensity = self.settings.contour_intensity as f32;
+		uniforms.decorations = self.settings.decorations;
+		uniforms.coloring = self.settings.coloring;
+		uniforms.grid_mode = self.settings.grid_mode;
+		uniforms.iteration_budget = self.settings.iteration_budget;
+		uniforms.modulus_base = self.settings.modulus_base as f32;
+		uniforms.phase_sectors = self.settings.phase_sectors;
+	}
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::WgpuState;
+
+/// How a value moves from one keyframe to the next
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+	#[default]
+	Linear,
+	/// Start slowly and speed up
+	EaseIn,
+	/// Start quickly and slow down
+	EaseOut,
+	/// Start and end slowly
+	EaseInOut,
+	/// Keep the value until the next keyframe
+	Hold,
+}
+
+impl Easing {
+	/// Map the fraction of the way between two keyframes
+	/// to the fraction of the way between their values
+	pub fn apply(self, x: f64) -> f64 {
+		let x = x.clamp(0.0, 1.0);
+		match self {
+			Easing::Linear => x,
+			Easing::EaseIn => x*x,
+			Easing::EaseOut => 1.0 - (1.0 - x)*(1.0 - x),
+			Easing::EaseInOut => x*x*(3.0 - 2.0*x),
+			Easing::Hold => 0.0,
+		}
+	}
+}
+
+/// A value at a time in seconds. The easing is used between
+/// this keyframe and the next one
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+	pub time: f64,
+	pub value: T,
+	#[serde(default)]
+	pub easing: Easing,
+}
+
+/// Values that can be interpolated between keyframes
+pub trait Interpolate: Copy {
+	fn lerp(self, other: Self, a: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+	fn lerp(self, other: Self, a: f64) -> Self {
+		self + (other - self)*a
+	}
+}
+
+impl<const N: usize> Interpolate for [f64; N] {
+	fn lerp(self, other: Self, a: f64) -> Self {
+		std::array::from_fn(|i| self[i].lerp(other[i], a))
+	}
+}
+
+/// The value at a time, holding the first and last values outside the keyframes.
+/// The keyframes must be sorted by time, see `Timeline::validate`
+fn sample<T: Interpolate>(keys: &[Keyframe<T>], time: f64) -> Option<T> {
+	let next = keys.partition_point(|key| key.time <= time);
+	match (next.checked_sub(1).map(|i| &keys[i]), keys.get(next)) {
+		(None, None) => None,
+		(Some(key), None) | (None, Some(key)) => Some(key.value),
+		(Some(a), Some(b)) => {
+			let x = (time - a.time) / (b.time - a.time);
+			Some(a.value.lerp(b.value, a.easing.apply(x)))
+		}
+	}
+}
+
+/// Settings of the renderer that can be animated. Those that choose between
+/// modes are rounded to the nearest integer and do not ease between keyframes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Setting {
+	ResScale,
+	ShadingIntensity,
+	ContourIntensity,
+	Decorations,
+	Coloring,
+	GridMode,
+	ModulusBase,
+	PhaseSectors,
+}
+
+impl Setting {
+	fn is_discrete(self) -> bool {
+		matches!(self, Setting::Decorations | Setting::Coloring | Setting::GridMode | Setting::PhaseSectors)
+	}
+
+	fn apply(self, state: &mut WgpuState, value: f64) {
+		let u = &mut state.uniforms;
+		match self {
+			Setting::ResScale => u.res_scale = value as f32,
+			Setting::ShadingIntensity => u.shading_intensity = value as f32,
+			Setting::ContourIntensity => u.contour_intensity = value as f32,
+			Setting::Decorations => u.decorations = value.round().max(0.0) as u32,
+			Setting::Coloring => u.coloring = value.round().max(0.0) as u32,
+			Setting::GridMode => u.grid_mode = value.round().max(0.0) as u32,
+			Setting::ModulusBase => u.modulus_base = value as f32,
+			Setting::PhaseSectors => u.phase_sectors = value.round().max(1.0) as u32,
+		}
+	}
+}
+
+/// The keyframes of one animated value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "track", rename_all = "snake_case")]
+pub enum Track {
+	/// A user variable by name, as its real and imaginary parts
+	Variable { name: String, keys: Vec<Keyframe<[f64; 2]>> },
+	/// The view, as the corners `[min re, min im, max re, max im]`
+	Bounds { keys: Vec<Keyframe<[f64; 4]>> },
+	Setting { setting: Setting, keys: Vec<Keyframe<f64>> },
+}
+
+impl Track {
+	fn times(&self) -> Vec<f64> {
+		match self {
+			Track::Variable { keys, .. } => keys.iter().map(|k| k.time).collect(),
+			Track::Bounds { keys } => keys.iter().map(|k| k.time).collect(),
+			Track::Setting { keys, .. } => keys.iter().map(|k| k.time).collect(),
+		}
+	}
+
+	fn description(&self) -> String {
+		match self {
+			Track::Variable { name, .. } => format!("variable {name}"),
+			Track::Bounds { .. } => "bounds".to_owned(),
+			Track::Setting { setting, .. } => format!("setting {setting:?}"),
+		}
+	}
+}
+
+/// Keyframed animation of user variables, the view and settings,
+/// which is sampled at a fixed frame rate to render an animation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+	/// Length in seconds
+	pub duration: f64,
+	/// Frames per second
+	pub fps: f64,
+	#[serde(default)]
+	pub tracks: Vec<Track>,
+}
+
+impl Timeline {
+	pub fn new(duration: f64, fps: f64) -> Self {
+		Self { duration, fps, tracks: Vec::new() }
+	}
+
+	/// Check that the timeline can be sampled, with keyframes in order of time
+	pub fn validate(&self) -> Result<(), String> {
+		if !(self.duration.is_finite() && self.duration >= 0.0) {
+			return Err(format!("duration {} must be a non-negative number", self.duration))
+		}
+		if !(self.fps.is_finite() && self.fps > 0.0) {
+			return Err(format!("frame rate {} must be positive", self.fps))
+		}
+		for track in &self.tracks {
+			let times = track.times();
+			if times.is_empty() {
+				return Err(format!("track for {} has no keyframes", track.description()))
+			}
+			if times.iter().any(|t| !t.is_finite()) {
+				return Err(format!("track for {} has a keyframe at an invalid time", track.description()))
+			}
+			if times.windows(2).any(|w| w[0] >= w[1]) {
+				return Err(format!("keyframes for {} are not in order of time", track.description()))
+			}
+		}
+		Ok(())
+	}
+
+	/// Number of frames to render, including one at time zero
+	pub fn frame_count(&self) -> u32 {
+		(self.duration * self.fps).floor() as u32 + 1
+	}
+
+	/// The time in seconds of a frame
+	pub fn frame_time(&self, frame: u32) -> f64 {
+		frame as f64 / self.fps
+	}
+
+	/// Set the state to the given frame. `vars` gives the slot of each user
+	/// variable, and tracks for variables not in it are ignored
+	pub fn apply(&self, state: &mut WgpuState, vars: &HashMap<String, usize>, frame: u32) {
+		let time = self.frame_time(frame);
+		state.set_time(time as f32, frame);
+		for track in &self.tracks {
+			match track {
+				Track::Variable { name, keys } => {
+					let slot = vars.get(name).and_then(|slot| state.variables.get_mut(2*slot..2*slot + 2));
+					if let (Some(slot), Some([re, im])) = (slot, sample(keys, time)) {
+						slot[0] = re as f32;
+						slot[1] = im as f32;
+					}
+				}
+				Track::Bounds { keys } => {
+					if let Some([x0, y0, x1, y1]) = sample(keys, time) {
+						state.uniforms.bounds_min = (x0 as f32, y0 as f32);
+						state.uniforms.bounds_max = (x1 as f32, y1 as f32);
+					}
+				}
+				Track::Setting { setting, keys } => {
+					let value = if setting.is_discrete() {
+						let next = keys.partition_point(|key| key.time <= time);
+						keys.get(next.saturating_sub(1)).map(|key| key.value)
+					} else {
+						sample(keys, time)
+					};
+					if let Some(value) = value {
+						setting.apply(state, value);
+					}
+				}
+			}
+		}
+	}
+}